@@ -1,7 +1,11 @@
 pub mod arknights {
+    use crate::banner::banner::{record, BannerModel, PityState, PullOutcome, Rarity};
     use rand::Rng;
-    use rand::thread_rng;
+    use rand::RngCore;
     use lazy_static::lazy_static;
+    use serde::ser::SerializeStruct;
+    use serde::{Deserialize, Serialize, Serializer};
+    use std::str::FromStr;
 
     lazy_static! {
         static ref SIX_STAR_RATE: f64 = 0.02;
@@ -10,71 +14,664 @@ pub mod arknights {
         static ref THREE_STAR_RATE: f64 = 0.4;
         static ref INCREMENT: f64 = 0.02;
         static ref SOFT_PITY: i32 = 50;
+        static ref ORUNDUM_PER_PULL: i32 = 600;
+        static ref FIRST_TEN_PULLS: i32 = 10;
+
+        // Limited banner: two featured operators share 70%, past limited operators weigh 5x off-banner
+        static ref LIMITED: HeadhuntingDefinition = HeadhuntingDefinition {
+            featured_share: 0.7,
+            featured_count: 2,
+            off_banner_count: 60,
+            past_limited_count: 10,
+            past_limited_weight: 5.0,
+            spark_pulls: Some(300),
+            shared_pity: false,
+        };
+
+        // Event banner: a single featured operator at 50%
+        static ref EVENT: HeadhuntingDefinition = HeadhuntingDefinition {
+            featured_share: 0.5,
+            featured_count: 1,
+            off_banner_count: 60,
+            past_limited_count: 0,
+            past_limited_weight: 1.0,
+            spark_pulls: Some(300),
+            shared_pity: false,
+        };
+
+        // Standard banner: two featured operators share 50%, pity carries over between standard banners
+        static ref STANDARD: HeadhuntingDefinition = HeadhuntingDefinition {
+            featured_share: 0.5,
+            featured_count: 2,
+            off_banner_count: 60,
+            past_limited_count: 0,
+            past_limited_weight: 1.0,
+            spark_pulls: None,
+            shared_pity: true,
+        };
+    }
+
+    /// Base rarity rates and soft pity rules for Arknights headhunting
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct ArknightsRates {
+        pub six_star_rate: f64,
+        pub five_star_rate: f64,
+        pub four_star_rate: f64,
+        pub three_star_rate: f64,
+        /// 6-star rate added for every pull past soft pity
+        pub increment: f64,
+        /// Soft pity starts after this many pulls without a 6-star
+        pub soft_pity: i32,
+        /// Whether each banner's first ten pulls include at least one 5-star or better
+        #[serde(default = "default_first_ten_guarantee")]
+        pub first_ten_guarantee: bool,
+    }
+
+    fn default_first_ten_guarantee() -> bool {
+        true
+    }
+
+    impl Default for ArknightsRates {
+        fn default() -> Self {
+            ArknightsRates {
+                six_star_rate: *SIX_STAR_RATE,
+                five_star_rate: *FIVE_STAR_RATE,
+                four_star_rate: *FOUR_STAR_RATE,
+                three_star_rate: *THREE_STAR_RATE,
+                increment: *INCREMENT,
+                soft_pity: *SOFT_PITY,
+                first_ten_guarantee: true,
+            }
+        }
+    }
+
+    impl ArknightsRates {
+        fn base_rates(&self) -> [f64; 4] {
+            [self.six_star_rate, self.five_star_rate, self.four_star_rate, self.three_star_rate]
+        }
+
+        /// Checks that the rarity rates form a probability distribution
+        pub fn validate(&self) -> Result<(), String> {
+            let rates = self.base_rates();
+            if rates.iter().chain([self.increment].iter()).any(|rate| !(0.0..=1.0).contains(rate)) {
+                return Err("rates and increment must be between 0 and 1".to_string());
+            }
+            let total: f64 = rates.iter().sum();
+            if (total - 1.0).abs() > 1e-9 {
+                return Err(format!("rarity rates must sum to 1, got {}", total));
+            }
+            if self.soft_pity < 0 {
+                return Err(format!("soft_pity must not be negative, got {}", self.soft_pity));
+            }
+            Ok(())
+        }
+    }
+
+    /// Rate-up rules of an Arknights headhunting banner
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct HeadhuntingDefinition {
+        /// Share of 6-stars that are featured operators
+        pub featured_share: f64,
+        /// Featured 6-star operators splitting the featured share equally; the first is the one pulled for
+        pub featured_count: i32,
+        /// Off-banner 6-star operators at normal weight
+        pub off_banner_count: i32,
+        /// Past limited operators in the off-banner pool
+        #[serde(default)]
+        pub past_limited_count: i32,
+        /// Weight of each past limited operator relative to a normal off-banner operator
+        #[serde(default = "default_past_limited_weight")]
+        pub past_limited_weight: f64,
+        /// Pulls on the banner after which the chosen featured operator can be exchanged for
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub spark_pulls: Option<i32>,
+        /// Whether the banner shares the standard pool's pity counter instead of starting its own
+        #[serde(default)]
+        pub shared_pity: bool,
+    }
+
+    fn default_past_limited_weight() -> f64 {
+        1.0
+    }
+
+    /// Built-in headhunting banners, in simulation order
+    pub fn builtin_headhunting() -> Vec<(String, HeadhuntingDefinition)> {
+        vec![
+            ("limited".to_string(), LIMITED.clone()),
+            ("event".to_string(), EVENT.clone()),
+            ("standard".to_string(), STANDARD.clone()),
+        ]
+    }
+
+    impl HeadhuntingDefinition {
+        /// Checks the featured share, pool sizes and spark
+        pub fn validate(&self) -> Result<(), String> {
+            if !(0.0..=1.0).contains(&self.featured_share) {
+                return Err(format!("featured_share must be between 0 and 1, got {}", self.featured_share));
+            }
+            if self.featured_count < 1 {
+                return Err("featured_count must be at least 1".to_string());
+            }
+            if self.off_banner_count < 0 || self.past_limited_count < 0 || self.past_limited_weight < 0.0 {
+                return Err("off-banner counts and past_limited_weight must not be negative".to_string());
+            }
+            if self.featured_share < 1.0 && self.off_banner_count + self.past_limited_count == 0 {
+                return Err("the off-banner pool is empty but featured_share is below 1".to_string());
+            }
+            if self.spark_pulls.is_some_and(|pulls| pulls < 1) {
+                return Err("spark_pulls must be at least 1".to_string());
+            }
+            // Otherwise a simulated player would pull forever for the chosen operator
+            if self.featured_share == 0.0 && self.spark_pulls.is_none() {
+                return Err("featured_share is 0 and there is no spark, so the featured operator can never be obtained".to_string());
+            }
+            Ok(())
+        }
+
+        /// Chance that an off-banner 6-star is one of the past limited operators
+        pub fn past_limited_share(&self) -> f64 {
+            let past_limited = self.past_limited_count as f64 * self.past_limited_weight;
+            let total = past_limited + self.off_banner_count as f64;
+            if total > 0.0 { past_limited / total } else { 0.0 }
+        }
+    }
+
+    /// Arknights headhunting banner with renormalised 6-star soft pity
+    pub struct ArknightsBanner {
+        rates: ArknightsRates,
+        /// Rate-up rules; without them no 6-star is featured
+        headhunting: Option<HeadhuntingDefinition>,
+        current_pity: i32,
+        current_rates: [f64; 4], // [6-star, 5-star, 4-star, 3-star]
+        /// Pulls on the current banner, for the first-ten guarantee
+        banner_pulls: i32,
+        /// Whether the current banner has given a 5-star or better yet
+        five_star_obtained: bool,
+    }
+
+    impl ArknightsBanner {
+        pub fn new(rates: &ArknightsRates) -> Self {
+            ArknightsBanner {
+                rates: rates.clone(),
+                headhunting: None,
+                current_pity: 0,
+                current_rates: rates.base_rates(),
+                banner_pulls: 0,
+                five_star_obtained: false,
+            }
+        }
+
+        /// Banner whose 6-stars are rolled against the given rate-up
+        pub fn headhunting(rates: &ArknightsRates, definition: &HeadhuntingDefinition) -> Self {
+            ArknightsBanner {
+                headhunting: Some(definition.clone()),
+                ..ArknightsBanner::new(rates)
+            }
+        }
+
+        /// Switches to another banner's rate-up while keeping the current pity.
+        /// The new banner has its own first-ten guarantee.
+        pub fn set_headhunting(&mut self, definition: &HeadhuntingDefinition) {
+            self.headhunting = Some(definition.clone());
+            self.banner_pulls = 0;
+            self.five_star_obtained = false;
+        }
+
+        fn reset_pity(&mut self) {
+            self.current_pity = 0;
+            self.current_rates = self.rates.base_rates();
+        }
+    }
+
+    impl BannerModel for ArknightsBanner {
+        fn pull(&mut self, rng: &mut dyn RngCore) -> PullOutcome {
+            let current_rates = &mut self.current_rates;
+            self.current_pity += 1;
+            self.banner_pulls += 1;
+
+            // Apply soft pity mechanism
+            if self.current_pity > self.rates.soft_pity {
+                // Calculate new 6-star rate
+                let new_six_star_rate = current_rates[0] + self.rates.increment;
+
+                // Calculate how much to reduce from other rates proportionally
+                let reduction = new_six_star_rate - current_rates[0];
+                let total_other_rates = current_rates[1] + current_rates[2] + current_rates[3];
+
+                // Apply proportional reductions to maintain sum = 1.0
+                current_rates[0] = new_six_star_rate;
+                current_rates[1] -= reduction * (current_rates[1] / total_other_rates);
+                current_rates[2] -= reduction * (current_rates[2] / total_other_rates);
+                current_rates[3] -= reduction * (current_rates[3] / total_other_rates);
+            }
+
+            // Generate random roll
+            let roll: f64 = rng.gen::<f64>();
+
+            // Determine result based on cumulative probability
+            if roll < current_rates[0] {
+                // 6-star, reset pity and rates to initial values
+                self.reset_pity();
+                self.five_star_obtained = true;
+                return match &self.headhunting {
+                    Some(definition) if rng.gen::<f64>() < definition.featured_share => {
+                        if definition.featured_count == 1 || rng.gen_range(0..definition.featured_count) == 0 {
+                            PullOutcome::new(Rarity::Six, true)
+                        } else {
+                            PullOutcome::other_featured(Rarity::Six)
+                        }
+                    }
+                    _ => PullOutcome::new(Rarity::Six, false),
+                };
+            }
+
+            // The tenth pull on a banner is at least a 5-star if none came before it
+            let guaranteed = self.rates.first_ten_guarantee && self.banner_pulls == *FIRST_TEN_PULLS && !self.five_star_obtained;
+            if guaranteed || roll < current_rates[0] + current_rates[1] {
+                self.five_star_obtained = true;
+                PullOutcome::new(Rarity::Five, false)
+            } else if roll < current_rates[0] + current_rates[1] + current_rates[2] {
+                PullOutcome::new(Rarity::Four, false)
+            } else {
+                PullOutcome::new(Rarity::Three, false)
+            }
+        }
+
+        fn pity(&self) -> PityState {
+            PityState {
+                pity: self.current_pity,
+                ..PityState::default()
+            }
+        }
+
+        fn reset(&mut self) {
+            self.reset_pity();
+            self.banner_pulls = 0;
+            self.five_star_obtained = false;
+        }
+    }
+
+    /// How a player buys headhunting pulls
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Purchase {
+        Single,
+        /// Ten pulls at a time; a player who reaches their goal mid-batch still makes the rest
+        Ten,
+    }
+
+    impl FromStr for Purchase {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "single" => Ok(Purchase::Single),
+                "ten" => Ok(Purchase::Ten),
+                _ => Err(format!("unknown purchase '{}', expected single or ten", s)),
+            }
+        }
+    }
+
+    /// Currency that headhunting costs are reported in
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Currency {
+        Orundum,
+        /// Headhunting permits; a ten-roll permit counts as ten
+        Permits,
+    }
+
+    impl FromStr for Currency {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "orundum" => Ok(Currency::Orundum),
+                "permits" => Ok(Currency::Permits),
+                _ => Err(format!("unknown currency '{}', expected orundum or permits", s)),
+            }
+        }
     }
 
-    pub fn a_simulate_game(num_simulations: i32) -> Vec<(i32, i32, i32, i32, i32)> {
-        let mut results: Vec<(i32, i32, i32, i32, i32)> = Vec::new();
+    /// How pulls are bought and what their cost is reported in
+    #[derive(Clone, Copy, Debug)]
+    pub struct Spending {
+        pub purchase: Purchase,
+        pub currency: Currency,
+    }
+
+    impl Spending {
+        /// Whether a player who has reached their goal after this many pulls can stop pulling
+        fn can_stop(&self, pulls: i32) -> bool {
+            match self.purchase {
+                Purchase::Single => true,
+                Purchase::Ten => pulls % 10 == 0,
+            }
+        }
+
+        /// Pulls actually bought to make the given number of pulls: ten-rolls round up
+        pub fn pulls_bought(&self, pulls: i32) -> i32 {
+            match self.purchase {
+                Purchase::Single => pulls,
+                Purchase::Ten => (pulls + 9) / 10 * 10,
+            }
+        }
+
+        /// Cost of the given number of pulls
+        pub fn cost(&self, pulls: i32) -> i32 {
+            match self.currency {
+                Currency::Orundum => pulls * *ORUNDUM_PER_PULL,
+                Currency::Permits => pulls,
+            }
+        }
+    }
+
+    // Serialized with the cost of a single pull, so run metadata records the price used
+    impl Serialize for Spending {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("Spending", 3)?;
+            state.serialize_field("purchase", &self.purchase)?;
+            state.serialize_field("currency", &self.currency)?;
+            state.serialize_field("cost_per_pull", &self.cost(1))?;
+            state.end()
+        }
+    }
+
+    record! {
+        /// One player's pulls on the standard headhunting banner until their first 6-star
+        pub struct ArknightsRecord("arknights") {
+            pulls: "Pulls" => "Total pulls until the first 6-star",
+            six_star: "SixStar" => "6-stars received",
+            five_star: "FiveStar" => "5-stars received",
+            four_star: "FourStar" => "4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            cost: "Cost" => "Orundum or permits spent on the pulls",
+        }
+    }
+
+    record! {
+        /// One player's pulls on a headhunting banner until they obtain the chosen featured operator
+        pub struct HeadhuntingRecord("headhunting") {
+            pulls: "Pulls" => "Total pulls until the chosen operator was obtained",
+            six_star: "SixStar" => "6-stars received",
+            featured: "Featured" => "Featured 6-stars received",
+            past_limited: "PastLimited" => "Past limited 6-stars received",
+            five_star: "FiveStar" => "5-stars received",
+            four_star: "FourStar" => "4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            spark: "Spark" => "1 if the chosen operator came from the spark, 0 if pulled",
+            cost: "Cost" => "Orundum or permits spent on the pulls",
+        }
+    }
+
+    record! {
+        /// One banner of one player's headhunting sequence
+        pub struct SequenceRecord("arknights_sequence") {
+            banner: "Banner" => "Position of the banner in the sequence, starting at 0",
+            start_pity: "StartPity" => "6-star pity the banner started with",
+            pulls: "Pulls" => "Pulls spent on the banner",
+            six_star: "SixStar" => "6-stars received",
+            featured: "Featured" => "Featured 6-stars received",
+            chosen: "Chosen" => "Copies of the chosen featured operator received",
+            five_star: "FiveStar" => "5-stars received",
+            four_star: "FourStar" => "4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            cost: "Cost" => "Orundum or permits spent on the banner",
+        }
+    }
+
+    /// Simulates players pulling until their first 6-star and can stop buying
+    pub fn a_simulate_game(rates: &ArknightsRates, spending: &Spending, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<ArknightsRecord> {
+        let mut results = Vec::new();
+        let mut banner = ArknightsBanner::new(rates);
 
         for _ in 0..num_simulations {
             let mut pull_count = 0;
-            let mut current_pity = 0;
             let mut pulls = [0, 0, 0, 0]; // [6-star, 5-star, 4-star, 3-star]
-            let mut six_star_obtained = false;
 
-            let mut current_rates = [*SIX_STAR_RATE, *FIVE_STAR_RATE, *FOUR_STAR_RATE, *THREE_STAR_RATE];
+            banner.reset();
 
-            // Pull until we get at least one 6-star
-            while !six_star_obtained {
-                current_pity += 1;
+            // Pull until we get at least one 6-star and can stop buying
+            loop {
                 pull_count += 1;
 
-                // Apply soft pity mechanism
-                if current_pity > *SOFT_PITY {
-                    // Calculate new 6-star rate
-                    let new_six_star_rate = current_rates[0] + *INCREMENT;
+                match banner.pull(rng).rarity {
+                    Rarity::Six => pulls[0] += 1,
+                    Rarity::Five => pulls[1] += 1,
+                    Rarity::Four => pulls[2] += 1,
+                    Rarity::Three => pulls[3] += 1,
+                }
+
+                if pulls[0] > 0 && spending.can_stop(pull_count) {
+                    break; // We got a 6-star, so we can stop pulling
+                }
+            }
+
+            // Add results for this simulation (how many pulls it took to get a 6-star)
+            results.push(ArknightsRecord {
+                pulls: pull_count,
+                six_star: pulls[0],
+                five_star: pulls[1],
+                four_star: pulls[2],
+                three_star: pulls[3],
+                cost: spending.cost(pull_count),
+            });
+        }
+
+        results
+    }
+
+    /// Simulates players pulling on a headhunting banner until they obtain the chosen featured
+    /// operator, either by pulling it or through the spark
+    pub fn a_simulate_headhunting(rates: &ArknightsRates, definition: &HeadhuntingDefinition, spending: &Spending, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<HeadhuntingRecord> {
+        let mut results = Vec::with_capacity(num_simulations as usize);
+        let mut banner = ArknightsBanner::headhunting(rates, definition);
+        let past_limited_share = definition.past_limited_share();
+
+        for _ in 0..num_simulations {
+            let mut pull_count = 0;
+            let mut pulls = [0, 0, 0, 0]; // [6-star, 5-star, 4-star, 3-star]
+            let mut featured = 0;
+            let mut past_limited = 0;
+            let mut spark = 0;
+            let mut obtained = false;
+
+            banner.reset();
 
-                    // Calculate how much to reduce from other rates proportionally
-                    let reduction = new_six_star_rate - current_rates[0];
-                    let total_other_rates = current_rates[1] + current_rates[2] + current_rates[3];
+            loop {
+                pull_count += 1;
+                let outcome = banner.pull(rng);
 
-                    // Apply proportional reductions to maintain sum = 1.0
-                    current_rates[0] = new_six_star_rate;
-                    current_rates[1] -= reduction * (current_rates[1] / total_other_rates);
-                    current_rates[2] -= reduction * (current_rates[2] / total_other_rates);
-                    current_rates[3] -= reduction * (current_rates[3] / total_other_rates);
+                match outcome.rarity {
+                    Rarity::Six => {
+                        pulls[0] += 1;
+                        if outcome.featured {
+                            featured += 1;
+                        } else if rng.gen::<f64>() < past_limited_share {
+                            past_limited += 1;
+                        }
+                    }
+                    Rarity::Five => pulls[1] += 1,
+                    Rarity::Four => pulls[2] += 1,
+                    Rarity::Three => pulls[3] += 1,
                 }
 
-                // Generate random roll
-                let roll: f64 = thread_rng().gen::<f64>();
-
-                // Determine result based on cumulative probability
-                if roll < current_rates[0] {
-                    // 6-star
-                    current_pity = 0;
-                    pulls[0] += 1;
-                    six_star_obtained = true; // We got a 6-star, so we can stop pulling
-
-                    // Reset rates to initial values
-                    current_rates[0] = *SIX_STAR_RATE;
-                    current_rates[1] = *FIVE_STAR_RATE;
-                    current_rates[2] = *FOUR_STAR_RATE;
-                    current_rates[3] = *THREE_STAR_RATE;
-                } else if roll < current_rates[0] + current_rates[1] {
-                    // 5-star
-                    pulls[1] += 1;
-                } else if roll < current_rates[0] + current_rates[1] + current_rates[2] {
-                    // 4-star
-                    pulls[2] += 1;
-                } else {
-                    // 3-star
-                    pulls[3] += 1;
+                if !obtained && outcome.chosen {
+                    obtained = true;
+                }
+                if !obtained && definition.spark_pulls == Some(pull_count) {
+                    obtained = true;
+                    spark = 1;
+                }
+                if obtained && spending.can_stop(pull_count) {
+                    break;
                 }
             }
 
-            // Add results for this simulation (how many pulls it took to get a 6-star)
-            results.push((pull_count, pulls[0], pulls[1], pulls[2], pulls[3]));
+            results.push(HeadhuntingRecord {
+                pulls: pull_count,
+                six_star: pulls[0],
+                featured,
+                past_limited,
+                five_star: pulls[1],
+                four_star: pulls[2],
+                three_star: pulls[3],
+                spark,
+                cost: spending.cost(pull_count),
+            });
+        }
+
+        results
+    }
+
+    /// One banner of a headhunting sequence and the pulls spent on it
+    #[derive(Clone, Debug, Serialize)]
+    pub struct SequenceStep {
+        pub name: String,
+        pub definition: HeadhuntingDefinition,
+        pub pulls: i32,
+    }
+
+    /// Parses a sequence such as "standard:50,limited:300" against the configured banners
+    pub fn parse_sequence(spec: &str, banners: &[(String, HeadhuntingDefinition)]) -> Result<Vec<SequenceStep>, String> {
+        spec.split(',')
+            .map(|step| {
+                let (name, pulls) = step.trim().split_once(':')
+                    .ok_or_else(|| format!("expected <banner>:<pulls>, got '{}'", step.trim()))?;
+                let definition = banners.iter()
+                    .find(|(existing, _)| existing == name)
+                    .map(|(_, definition)| definition.clone())
+                    .ok_or_else(|| format!("unknown headhunting banner '{}'", name))?;
+                let pulls = pulls.parse::<i32>()
+                    .ok()
+                    .filter(|&pulls| pulls >= 1)
+                    .ok_or_else(|| format!("invalid pull count '{}' for banner '{}'", pulls, name))?;
+                Ok(SequenceStep { name: name.to_string(), definition, pulls })
+            })
+            .collect()
+    }
+
+    /// Simulates players spending a fixed number of pulls on each banner of the sequence in turn.
+    /// Banners with shared pity continue the standard pool's counter and the others start their
+    /// own. Each player produces one record per banner. Every banner in the sequence has its own
+    /// first-ten guarantee. Players buying ten at a time round each banner's pulls up to whole
+    /// ten-rolls.
+    pub fn a_simulate_sequence(rates: &ArknightsRates, sequence: &[SequenceStep], spending: &Spending, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<SequenceRecord> {
+        let mut results = Vec::with_capacity(num_simulations as usize * sequence.len());
+        let mut standard_pool = ArknightsBanner::new(rates);
+        let mut separate = ArknightsBanner::new(rates);
+
+        for _ in 0..num_simulations {
+            standard_pool.reset();
+
+            for (index, step) in sequence.iter().enumerate() {
+                let banner = if step.definition.shared_pity {
+                    &mut standard_pool
+                } else {
+                    separate.reset();
+                    &mut separate
+                };
+                banner.set_headhunting(&step.definition);
+                let start_pity = banner.pity().pity;
+
+                let mut rarities = [0, 0, 0, 0]; // [6-star, 5-star, 4-star, 3-star]
+                let mut featured = 0;
+                let mut chosen = 0;
+                let pulls = spending.pulls_bought(step.pulls);
+                for _ in 0..pulls {
+                    let outcome = banner.pull(rng);
+                    match outcome.rarity {
+                        Rarity::Six => {
+                            rarities[0] += 1;
+                            if outcome.featured {
+                                featured += 1;
+                            }
+                            if outcome.chosen {
+                                chosen += 1;
+                            }
+                        }
+                        Rarity::Five => rarities[1] += 1,
+                        Rarity::Four => rarities[2] += 1,
+                        Rarity::Three => rarities[3] += 1,
+                    }
+                }
+
+                results.push(SequenceRecord {
+                    banner: index as i32,
+                    start_pity,
+                    pulls,
+                    six_star: rarities[0],
+                    featured,
+                    chosen,
+                    five_star: rarities[1],
+                    four_star: rarities[2],
+                    three_star: rarities[3],
+                    cost: spending.cost(pulls),
+                });
+            }
         }
 
         results
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::banner::banner::SimRng;
+        use rand::SeedableRng;
+
+        #[test]
+        fn rarity_counts_add_up_to_pulls() {
+            let mut rng = SimRng::seed_from_u64(1);
+            let rates = ArknightsRates::default();
+
+            for purchase in [Purchase::Single, Purchase::Ten] {
+                let spending = Spending { purchase, currency: Currency::Orundum };
+                for record in a_simulate_game(&rates, &spending, 500, &mut rng) {
+                    assert_eq!(record.six_star + record.five_star + record.four_star + record.three_star, record.pulls, "{:?}", record);
+                }
+                for (_, definition) in builtin_headhunting() {
+                    for record in a_simulate_headhunting(&rates, &definition, &spending, 500, &mut rng) {
+                        assert_eq!(record.six_star + record.five_star + record.four_star + record.three_star, record.pulls, "{:?}", record);
+                    }
+                }
+            }
+
+            let sequence = parse_sequence("standard:50,limited:125,event:30", &builtin_headhunting()).unwrap();
+            for purchase in [Purchase::Single, Purchase::Ten] {
+                let spending = Spending { purchase, currency: Currency::Orundum };
+                for record in a_simulate_sequence(&rates, &sequence, &spending, 500, &mut rng) {
+                    assert_eq!(record.six_star + record.five_star + record.four_star + record.three_star, record.pulls, "{:?}", record);
+                }
+            }
+        }
+
+        #[test]
+        fn sequence_cost_covers_whole_ten_rolls() {
+            let mut rng = SimRng::seed_from_u64(1);
+            let sequence = parse_sequence("limited:125,event:30", &builtin_headhunting()).unwrap();
+            let spending = Spending { purchase: Purchase::Ten, currency: Currency::Permits };
+            let records = a_simulate_sequence(&ArknightsRates::default(), &sequence, &spending, 1, &mut rng);
+            let costs: Vec<_> = records.iter().map(|record| (record.pulls, record.cost)).collect();
+            assert_eq!(costs, [(130, 130), (30, 30)]);
+        }
+
+        #[test]
+        fn sequence_steps_keep_their_order() {
+            let sequence = parse_sequence("standard:50, limited:300", &builtin_headhunting()).unwrap();
+            let steps: Vec<_> = sequence.iter().map(|step| (step.name.as_str(), step.pulls)).collect();
+            assert_eq!(steps, [("standard", 50), ("limited", 300)]);
+            assert!(sequence[0].definition.shared_pity);
+        }
+
+        #[test]
+        fn sequence_rejects_malformed_steps() {
+            let banners = builtin_headhunting();
+            for spec in ["", "limited", "limited:", "limited:0", "limited:-5", "limited:ten", "unknown:10", "limited:10,"] {
+                assert!(parse_sequence(spec, &banners).is_err(), "accepted '{}'", spec);
+            }
+        }
+    }
 }
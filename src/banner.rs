@@ -0,0 +1,254 @@
+pub mod banner {
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use serde::{Deserialize, Serialize};
+
+    /// Random number generator used by every simulation; its output is stable across platforms
+    pub type SimRng = ChaCha8Rng;
+
+    /// Derives the RNG for one chunk of a banner run.
+    /// The banner key selects the seed and the chunk index selects an independent ChaCha stream,
+    /// so a chunk's results depend only on the master seed, the banner and the chunk's position.
+    pub fn chunk_rng(master_seed: u64, banner_key: &str, chunk_index: u64) -> SimRng {
+        // FNV-1a over the banner key, mixed into the master seed
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in banner_key.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+
+        let mut rng = SimRng::seed_from_u64(master_seed ^ hash);
+        rng.set_stream(chunk_index);
+        rng
+    }
+
+    /// Name and meaning of one integer column of a simulation record
+    #[derive(Clone, Copy, Debug, Serialize)]
+    pub struct Column {
+        pub name: &'static str,
+        pub description: &'static str,
+    }
+
+    /// One simulated player's result, with a fixed set of named integer columns
+    pub trait Record {
+        /// Schema name shared by every output file holding this record
+        const NAME: &'static str;
+        /// Columns in output order
+        const COLUMNS: &'static [Column];
+
+        /// Appends the values in the same order as `COLUMNS`
+        fn push_values(&self, values: &mut Vec<i32>);
+    }
+
+    /// Declares a record struct whose fields, output columns and descriptions come from one list,
+    /// so headers always match the values written under them
+    macro_rules! record {
+        (
+            $(#[$meta:meta])*
+            $vis:vis struct $name:ident($schema:literal) {
+                $($field:ident: $column:literal => $description:literal,)*
+            }
+        ) => {
+            $(#[$meta])*
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+            $vis struct $name {
+                $(#[doc = $description] pub $field: i32,)*
+            }
+
+            impl $crate::banner::banner::Record for $name {
+                const NAME: &'static str = $schema;
+                const COLUMNS: &'static [$crate::banner::banner::Column] = &[
+                    $($crate::banner::banner::Column { name: $column, description: $description },)*
+                ];
+
+                fn push_values(&self, values: &mut Vec<i32>) {
+                    $(values.push(self.$field);)*
+                }
+            }
+        };
+    }
+    pub(crate) use record;
+
+    /// Rarity tiers shared by every supported game
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Rarity {
+        Three,
+        Four,
+        Five,
+        Six,
+    }
+
+    /// Result of a single pull on a banner
+    #[derive(Clone, Copy, Debug)]
+    pub struct PullOutcome {
+        pub rarity: Rarity,
+        /// Whether the item is one of the banner's featured (rate-up) items
+        pub featured: bool,
+        /// Whether the item is the specific featured item being pulled for; on banners with a
+        /// single featured item of this rarity this is the same as `featured`
+        pub chosen: bool,
+        /// Whether the item is a weapon (light cone, W-Engine) rather than a character
+        pub weapon: bool,
+    }
+
+    impl PullOutcome {
+        pub fn new(rarity: Rarity, featured: bool) -> Self {
+            PullOutcome { rarity, featured, chosen: featured, weapon: false }
+        }
+
+        /// A featured item that is not the one being pulled for
+        pub fn other_featured(rarity: Rarity) -> Self {
+            PullOutcome { rarity, featured: true, chosen: false, weapon: false }
+        }
+
+        /// Marks the item as a weapon or a character
+        pub fn with_weapon(self, weapon: bool) -> Self {
+            PullOutcome { weapon, ..self }
+        }
+    }
+
+    /// Snapshot of a banner's pity counters
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct PityState {
+        /// Pulls since the last item of the banner's highest rarity
+        pub pity: i32,
+        /// Pulls since the last 4-star or better
+        pub four_star_pity: i32,
+        /// Whether the next highest-rarity item is guaranteed to be featured
+        pub guaranteed: bool,
+        /// Points towards a guaranteed chosen item (Genshin's Epitomized Path fate points)
+        pub fate_points: i32,
+        /// Whether the next 4-star is guaranteed to be featured
+        pub four_star_guaranteed: bool,
+    }
+
+    /// 5-star and 4-star rates with their soft and hard pity. Banner definitions flatten this into
+    /// their own fields, so config files list the rates alongside the banner's other settings.
+    /// Wuthering Waves configs may name the 5-star rate and pity `five_star_rate` and `five_star_pity`.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct PityRates {
+        /// Base 5-star rate before soft pity
+        #[serde(alias = "five_star_rate")]
+        pub base_rate: f64,
+        /// Soft pity starts after this many pulls without a 5-star
+        #[serde(default)]
+        pub soft_pity: i32,
+        /// Rate added for every pull past soft pity
+        #[serde(default)]
+        pub soft_pity_increment: f64,
+        /// Pull count at which a 5-star is guaranteed
+        #[serde(alias = "five_star_pity")]
+        pub hard_pity: i32,
+        /// Base 4-star rate
+        pub four_star_rate: f64,
+        /// Pull count at which a 4-star is guaranteed
+        pub four_star_pity: i32,
+        /// Last 4-star pity count before the 4-star rate starts increasing
+        #[serde(default)]
+        pub four_star_soft_pity: i32,
+        /// 4-star rate added per pull past the 4-star soft pity
+        #[serde(default)]
+        pub four_star_soft_pity_increment: f64,
+    }
+
+    impl PityRates {
+        /// 5-star rate of a pull at the given pity (1-based), before hard pity is applied
+        pub fn five_star_chance(&self, pity: i32) -> f64 {
+            let mut chance = self.base_rate;
+            if pity > self.soft_pity {
+                chance += self.soft_pity_increment * (pity - self.soft_pity) as f64;
+            }
+            chance
+        }
+
+        /// 4-star rate of a pull at the given 4-star pity (1-based), before 4-star pity is applied
+        pub fn four_star_chance(&self, four_star_pity: i32) -> f64 {
+            let mut chance = self.four_star_rate;
+            if four_star_pity > self.four_star_soft_pity {
+                chance += self.four_star_soft_pity_increment * (four_star_pity - self.four_star_soft_pity) as f64;
+            }
+            chance
+        }
+
+        /// Checks that rates are probabilities and pity thresholds are consistent
+        pub fn validate(&self) -> Result<(), String> {
+            for (name, rate) in [
+                ("base_rate", self.base_rate),
+                ("soft_pity_increment", self.soft_pity_increment),
+                ("four_star_rate", self.four_star_rate),
+                ("four_star_soft_pity_increment", self.four_star_soft_pity_increment),
+            ] {
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(format!("{} must be between 0 and 1, got {}", name, rate));
+                }
+            }
+            if self.hard_pity < 1 || self.four_star_pity < 1 {
+                return Err("hard_pity and four_star_pity must be at least 1".to_string());
+            }
+            if self.soft_pity < 0 || self.soft_pity >= self.hard_pity {
+                return Err(format!("soft_pity must be between 0 and hard_pity ({}), got {}", self.hard_pity, self.soft_pity));
+            }
+            if self.four_star_soft_pity < 0 || self.four_star_soft_pity >= self.four_star_pity {
+                return Err(format!("four_star_soft_pity must be between 0 and four_star_pity ({}), got {}", self.four_star_pity, self.four_star_soft_pity));
+            }
+            Ok(())
+        }
+    }
+
+    /// A single gacha banner that can be pulled on one item at a time
+    pub trait BannerModel {
+        /// Performs one pull, updating the banner's pity state
+        fn pull(&mut self, rng: &mut dyn RngCore) -> PullOutcome;
+
+        /// Returns the current pity state
+        fn pity(&self) -> PityState;
+
+        /// Resets the banner to a fresh account with no pity
+        fn reset(&mut self);
+    }
+
+    record! {
+        /// Pulls from one 4-star pity reset to the next
+        pub struct FourStarGapRecord("four_star_gap") {
+            pulls: "Pulls" => "Pulls until the 4-star pity reset",
+            four_star: "FourStar" => "4-stars received",
+            five_star: "FiveStar" => "5-stars or better received",
+            three_star: "ThreeStar" => "3-stars received",
+        }
+    }
+
+    /// Pulls from a fresh banner until its 4-star pity resets, once per simulation
+    pub fn simulate_four_star_gaps(banner: &mut dyn BannerModel, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<FourStarGapRecord> {
+        let mut results = Vec::with_capacity(num_simulations as usize);
+
+        for _ in 0..num_simulations {
+            let mut pulls = 0;
+            let mut four_stars = 0;
+            let mut five_stars = 0;
+            let mut three_stars = 0;
+
+            banner.reset();
+
+            loop {
+                pulls += 1;
+                match banner.pull(rng).rarity {
+                    Rarity::Four => four_stars += 1,
+                    Rarity::Five | Rarity::Six => five_stars += 1,
+                    Rarity::Three => three_stars += 1,
+                }
+                if banner.pity().four_star_pity == 0 {
+                    break;
+                }
+            }
+
+            results.push(FourStarGapRecord {
+                pulls,
+                four_star: four_stars,
+                five_star: five_stars,
+                three_star: three_stars,
+            });
+        }
+
+        results
+    }
+}
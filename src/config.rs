@@ -0,0 +1,128 @@
+pub mod config {
+    use crate::arknights::arknights::{builtin_headhunting, ArknightsRates, HeadhuntingDefinition};
+    use crate::hoyo::hoyo::{builtin_games, GameData};
+    use crate::standard::standard::{builtin_standard, StandardDefinition};
+    use crate::wuwa::wuwa::WuwaRates;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    /// Banner definitions for every simulated game
+    pub struct BannerConfig {
+        /// HoYoverse games in simulation order
+        pub hoyo: Vec<(String, GameData)>,
+        pub arknights: ArknightsRates,
+        /// Arknights headhunting banners by name, in simulation order
+        pub headhunting: Vec<(String, HeadhuntingDefinition)>,
+        /// Wuthering Waves resonator convene
+        pub wuwa: WuwaRates,
+        /// Wuthering Waves weapon convene
+        pub wuwa_weapon: WuwaRates,
+        /// Standard banners by game name, in simulation order
+        pub standard: Vec<(String, StandardDefinition)>,
+    }
+
+    /// Layout of a banner definition file; every section is optional
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct BannerFile {
+        #[serde(default)]
+        hoyo: BTreeMap<String, GameData>,
+        arknights: Option<ArknightsRates>,
+        #[serde(default)]
+        headhunting: BTreeMap<String, HeadhuntingDefinition>,
+        wuwa: Option<WuwaRates>,
+        wuwa_weapon: Option<WuwaRates>,
+        #[serde(default)]
+        standard: BTreeMap<String, StandardDefinition>,
+    }
+
+    impl Default for BannerConfig {
+        fn default() -> Self {
+            BannerConfig {
+                hoyo: builtin_games(),
+                arknights: ArknightsRates::default(),
+                headhunting: builtin_headhunting(),
+                wuwa: WuwaRates::default(),
+                wuwa_weapon: WuwaRates::weapon(),
+                standard: builtin_standard(),
+            }
+        }
+    }
+
+    fn invalid_data(path: &Path, message: impl std::fmt::Display) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message))
+    }
+
+    /// Game and banner names become output paths, so they are limited to safe characters
+    fn validate_name(path: &Path, name: &str) -> io::Result<()> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(invalid_data(path, format!("invalid name '{}'", name)));
+        }
+        Ok(())
+    }
+
+    impl BannerConfig {
+        /// Loads a TOML or JSON banner definition file on top of the built-in definitions.
+        /// HoYoverse games, headhunting banners and standard banners with a built-in name replace
+        /// it, other names are added alongside the built-in ones.
+        pub fn load(path: &Path) -> io::Result<Self> {
+            let contents = fs::read_to_string(path)?;
+            let file: BannerFile = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("json") => serde_json::from_str(&contents).map_err(|e| invalid_data(path, e))?,
+                _ => toml::from_str(&contents).map_err(|e| invalid_data(path, e))?,
+            };
+
+            let mut config = BannerConfig::default();
+
+            for (name, game_data) in file.hoyo {
+                validate_name(path, &name)?;
+                game_data.validate().map_err(|e| invalid_data(path, format!("hoyo.{}: {}", name, e)))?;
+
+                match config.hoyo.iter_mut().find(|(existing, _)| *existing == name) {
+                    Some((_, existing)) => *existing = game_data,
+                    None => config.hoyo.push((name, game_data)),
+                }
+            }
+
+            if let Some(arknights) = file.arknights {
+                arknights.validate().map_err(|e| invalid_data(path, format!("arknights: {}", e)))?;
+                config.arknights = arknights;
+            }
+
+            for (name, definition) in file.headhunting {
+                validate_name(path, &name)?;
+                definition.validate().map_err(|e| invalid_data(path, format!("headhunting.{}: {}", name, e)))?;
+
+                match config.headhunting.iter_mut().find(|(existing, _)| *existing == name) {
+                    Some((_, existing)) => *existing = definition,
+                    None => config.headhunting.push((name, definition)),
+                }
+            }
+
+            if let Some(wuwa) = file.wuwa {
+                wuwa.validate().map_err(|e| invalid_data(path, format!("wuwa: {}", e)))?;
+                config.wuwa = wuwa;
+            }
+
+            if let Some(wuwa_weapon) = file.wuwa_weapon {
+                wuwa_weapon.validate().map_err(|e| invalid_data(path, format!("wuwa_weapon: {}", e)))?;
+                config.wuwa_weapon = wuwa_weapon;
+            }
+
+            for (name, definition) in file.standard {
+                validate_name(path, &name)?;
+                definition.validate().map_err(|e| invalid_data(path, format!("standard.{}: {}", name, e)))?;
+
+                match config.standard.iter_mut().find(|(existing, _)| *existing == name) {
+                    Some((_, existing)) => *existing = definition,
+                    None => config.standard.push((name, definition)),
+                }
+            }
+
+            Ok(config)
+        }
+    }
+}
@@ -0,0 +1,339 @@
+pub mod exact {
+    use crate::arknights::arknights::ArknightsRates;
+    use crate::banner::banner::PityState;
+    use crate::hoyo::hoyo::BannerDefinition;
+
+    // Stop iterating once the probability of still not having the item falls below this
+    const TAIL_TOLERANCE: f64 = 1e-12;
+    // Upper bound on distribution length for banners that may never give the featured item
+    const MAX_PULLS: usize = 10_000;
+
+    /// Chance that a pull at the given pity (1-based) is a 5-star, given no 5-star before it
+    fn five_star_hazard(definition: &BannerDefinition, pity: i32) -> f64 {
+        if pity >= definition.pity.hard_pity {
+            return 1.0;
+        }
+        definition.pity.five_star_chance(pity).clamp(0.0, 1.0)
+    }
+
+    /// Exact distribution of pulls until the banner's featured 5-star from the given pity state.
+    /// Element `i` is the probability that it takes exactly `i + 1` pulls.
+    ///
+    /// With Capturing Radiance the streak of lost featured rolls carries over between banners, so
+    /// the result is averaged over the streak's long-run distribution across consecutive banners.
+    pub fn pulls_to_featured(definition: &BannerDefinition, start: PityState) -> Vec<f64> {
+        let streaks = definition.capturing_radiance.map_or(1, |losses| losses as usize + 1);
+        if streaks == 1 {
+            return solve_banner(definition, start, 0).0;
+        }
+
+        // Banner-to-banner transitions of the lost streak, from fresh pity
+        let transitions: Vec<Vec<f64>> = (0..streaks)
+            .map(|streak| solve_banner(definition, PityState::default(), streak).1)
+            .collect();
+
+        // Power iteration for the stationary streak distribution
+        let mut stationary = vec![0.0; streaks];
+        stationary[0] = 1.0;
+        for _ in 0..1000 {
+            let mut next = vec![0.0; streaks];
+            for (from, &weight) in stationary.iter().enumerate() {
+                for (to, &probability) in transitions[from].iter().enumerate() {
+                    next[to] += weight * probability;
+                }
+            }
+            stationary = next;
+        }
+
+        let mut pmf: Vec<f64> = Vec::new();
+        for (streak, &weight) in stationary.iter().enumerate() {
+            let (streak_pmf, _) = solve_banner(definition, start, streak);
+            if pmf.len() < streak_pmf.len() {
+                pmf.resize(streak_pmf.len(), 0.0);
+            }
+            for (total, probability) in pmf.iter_mut().zip(streak_pmf) {
+                *total += weight * probability;
+            }
+        }
+        pmf
+    }
+
+    /// Solves one banner starting with the given lost streak. Returns the distribution of pulls to
+    /// the chosen featured 5-star and the distribution of the lost streak once it is obtained.
+    ///
+    /// The banner is a Markov chain over (featured rolls lost in a row, fate points, guarantee flag,
+    /// pulls since last 5-star); the chain is stepped one pull at a time and the mass absorbed by
+    /// the chosen featured 5-star is recorded. The lost streak only matters for Capturing Radiance
+    /// and fate points only for the Epitomized Path.
+    fn solve_banner(definition: &BannerDefinition, start: PityState, start_streak: usize) -> (Vec<f64>, Vec<f64>) {
+        let hard_pity = definition.pity.hard_pity as usize;
+        let radiance = definition.capturing_radiance.map(|losses| losses as usize);
+        let streaks = radiance.map_or(1, |losses| losses + 1);
+        let path = definition.epitomized_path.map(|points| points as usize);
+        let fate_levels = path.map_or(1, |points| points + 1);
+        let chosen_share = 1.0 / definition.featured_count as f64;
+        let index = |streak: usize, fate: usize, guaranteed: usize, pity: usize| {
+            ((streak * fate_levels + fate) * 2 + guaranteed) * hard_pity + pity
+        };
+
+        // mass[index(..)] = probability of being in that state without the chosen item
+        let mut mass = vec![0.0; streaks * fate_levels * 2 * hard_pity];
+        let start_fate = (start.fate_points as usize).min(fate_levels - 1);
+        mass[index(start_streak, start_fate, start.guaranteed as usize, start.pity as usize)] = 1.0;
+
+        let mut pmf = Vec::new();
+        let mut end_streaks = vec![0.0; streaks];
+        let mut remaining = 1.0;
+
+        while remaining > TAIL_TOLERANCE && pmf.len() < MAX_PULLS {
+            let mut next = vec![0.0; mass.len()];
+            let mut absorbed = 0.0;
+
+            for streak in 0..streaks {
+                for fate in 0..fate_levels {
+                    for guaranteed in 0..2 {
+                        for pity in 0..hard_pity {
+                            let probability = mass[index(streak, fate, guaranteed, pity)];
+                            if probability == 0.0 {
+                                continue;
+                            }
+
+                            let five_star = probability * five_star_hazard(definition, pity as i32 + 1);
+
+                            // No 5-star: pity advances
+                            if pity + 1 < hard_pity {
+                                next[index(streak, fate, guaranteed, pity + 1)] += probability - five_star;
+                            }
+
+                            // Epitomized Path: enough fate points give the chosen item outright
+                            if path.is_some_and(|points| fate >= points) {
+                                absorbed += five_star;
+                                end_streaks[streak] += five_star;
+                                continue;
+                            }
+
+                            // Featured roll: (probability of a featured item, lost streak afterwards)
+                            let (featured, won_streak) = if guaranteed == 1 {
+                                (five_star, streak)
+                            } else if radiance.is_some_and(|losses| streak >= losses) {
+                                (five_star, 0)
+                            } else {
+                                (five_star * definition.featured_chance, 0)
+                            };
+                            let lost = five_star - featured;
+
+                            let chosen = featured * chosen_share;
+                            absorbed += chosen;
+                            end_streaks[won_streak] += chosen;
+
+                            // Any other 5-star earns a fate point
+                            let next_fate = if path.is_some() { (fate + 1).min(fate_levels - 1) } else { 0 };
+                            next[index(won_streak, next_fate, 0, 0)] += featured - chosen;
+                            if lost > 0.0 {
+                                let lost_streak = (streak + 1).min(streaks - 1);
+                                next[index(lost_streak, next_fate, definition.guarantee as usize, 0)] += lost;
+                            }
+                        }
+                    }
+                }
+            }
+
+            pmf.push(absorbed);
+            remaining = next.iter().sum();
+            mass = next;
+        }
+
+        (pmf, end_streaks)
+    }
+
+    /// Exact Arknights results for pulling until the first 6-star
+    pub struct ArknightsDistribution {
+        /// Element `i` is the probability that the first 6-star takes exactly `i + 1` pulls
+        pub pmf: Vec<f64>,
+        pub expected_five_star: f64,
+        pub expected_four_star: f64,
+        pub expected_three_star: f64,
+    }
+
+    /// Exact distribution of pulls until the first Arknights 6-star from zero pity.
+    ///
+    /// Past soft pity the 6-star rate grows by the increment each pull and the other rarities
+    /// shrink proportionally, so at every pity their shares of the non-6-star mass stay fixed.
+    /// The first-ten guarantee only turns a 4-star or 3-star into a 5-star, so it changes the
+    /// expected rarity counts but not the 6-star distribution.
+    pub fn arknights_pulls_to_six_star(rates: &ArknightsRates) -> ArknightsDistribution {
+        let other_total = rates.five_star_rate + rates.four_star_rate + rates.three_star_rate;
+        let mut distribution = ArknightsDistribution {
+            pmf: Vec::new(),
+            expected_five_star: 0.0,
+            expected_four_star: 0.0,
+            expected_three_star: 0.0,
+        };
+
+        // Probability of still having no 6-star before the current pull
+        let mut survival = 1.0;
+        // Probability of having nothing better than a 4-star so far
+        let mut below_five_star = 1.0;
+        let low_total = rates.four_star_rate + rates.three_star_rate;
+        let mut pity = 0;
+
+        while survival > TAIL_TOLERANCE && distribution.pmf.len() < MAX_PULLS {
+            pity += 1;
+            let mut six_star_rate = rates.six_star_rate;
+            if pity > rates.soft_pity {
+                six_star_rate += rates.increment * (pity - rates.soft_pity) as f64;
+            }
+            let six_star_rate = six_star_rate.clamp(0.0, 1.0);
+
+            // Mass that does not become a 6-star is split in the base ratio of the other rarities
+            if other_total > 0.0 {
+                let other_mass = survival * (1.0 - six_star_rate) / other_total;
+                distribution.expected_five_star += other_mass * rates.five_star_rate;
+                distribution.expected_four_star += other_mass * rates.four_star_rate;
+                distribution.expected_three_star += other_mass * rates.three_star_rate;
+                below_five_star *= (1.0 - six_star_rate) * low_total / other_total;
+            }
+
+            // A tenth pull with nothing better than a 4-star so far becomes a 5-star
+            if rates.first_ten_guarantee && pity == 10 && low_total > 0.0 {
+                distribution.expected_five_star += below_five_star;
+                distribution.expected_four_star -= below_five_star * rates.four_star_rate / low_total;
+                distribution.expected_three_star -= below_five_star * rates.three_star_rate / low_total;
+            }
+
+            distribution.pmf.push(survival * six_star_rate);
+            survival *= 1.0 - six_star_rate;
+        }
+
+        distribution
+    }
+
+    /// Expected value of a pull-count distribution produced by this module
+    pub fn expected_pulls(pmf: &[f64]) -> f64 {
+        pmf.iter()
+            .enumerate()
+            .map(|(i, probability)| (i + 1) as f64 * probability)
+            .sum()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::banner::banner::SimRng;
+        use crate::hoyo::hoyo::{builtin_games, h_simulate_target, PullOrder, StartingState, Target};
+        use rand::SeedableRng;
+
+        fn assert_sums_to_one(pmf: &[f64]) {
+            let total: f64 = pmf.iter().sum();
+            assert!((total - 1.0).abs() < 1e-9, "distribution sums to {}", total);
+        }
+
+        /// Most 5-stars that can be needed before the chosen featured one
+        fn max_five_stars(definition: &BannerDefinition) -> Option<usize> {
+            match definition.epitomized_path {
+                Some(points) => Some(points as usize + 1),
+                None if definition.featured_count == 1 && definition.guarantee => Some(2),
+                None => None,
+            }
+        }
+
+        #[test]
+        fn hoyo_distributions_sum_to_one() {
+            for (game_name, game_data) in builtin_games() {
+                for definition in [&game_data.character, &game_data.weapon] {
+                    let pmf = pulls_to_featured(definition, PityState::default());
+                    assert_sums_to_one(&pmf);
+                    assert!(pmf.iter().all(|&probability| probability >= 0.0), "{} has negative mass", game_name);
+                }
+            }
+        }
+
+        #[test]
+        fn hoyo_distributions_end_at_hard_pity() {
+            for (game_name, game_data) in builtin_games() {
+                for definition in [&game_data.character, &game_data.weapon] {
+                    let hard_pity = definition.pity.hard_pity as usize;
+                    let limit = max_five_stars(definition).expect("built-in banners are bounded") * hard_pity;
+                    let pmf = pulls_to_featured(definition, PityState::default());
+                    assert!(pmf.len() <= limit, "{} needs {} pulls, more than {}", game_name, pmf.len(), limit);
+
+                    // With a single featured item, a guaranteed 5-star is the chosen one
+                    if definition.featured_count == 1 {
+                        let guaranteed = PityState { guaranteed: true, ..PityState::default() };
+                        let pmf = pulls_to_featured(definition, guaranteed);
+                        assert_sums_to_one(&pmf);
+                        assert!(pmf.len() <= hard_pity, "{} needs {} pulls from a guarantee", game_name, pmf.len());
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn starting_pity_shortens_the_distribution() {
+            let (_, game_data) = &builtin_games()[0];
+            let definition = &game_data.character;
+            let start = PityState { pity: 80, guaranteed: true, ..PityState::default() };
+            let pmf = pulls_to_featured(definition, start);
+            assert_sums_to_one(&pmf);
+            assert!(pmf.len() <= (definition.pity.hard_pity - 80) as usize);
+            assert!(expected_pulls(&pmf) < expected_pulls(&pulls_to_featured(definition, PityState::default())));
+        }
+
+        #[test]
+        fn always_featured_banner_is_plain_pity() {
+            let (_, game_data) = &builtin_games()[0];
+            let definition = BannerDefinition { featured_chance: 1.0, ..game_data.character.clone() };
+            let pmf = pulls_to_featured(&definition, PityState::default());
+            assert_sums_to_one(&pmf);
+            assert_eq!(pmf.len(), definition.pity.hard_pity as usize);
+            assert!((pmf[0] - definition.pity.base_rate).abs() < 1e-12);
+        }
+
+        #[test]
+        fn simulated_mean_matches_the_exact_expectation() {
+            let simulations = 10_000;
+            for (game_name, game_data) in builtin_games() {
+                for (banner_name, definition, target) in [
+                    ("character", &game_data.character, Target::new(1, 0, PullOrder::CharacterFirst)),
+                    ("weapon", &game_data.weapon, Target::new(0, 1, PullOrder::WeaponFirst)),
+                ] {
+                    let mut rng = SimRng::seed_from_u64(7);
+                    let records = h_simulate_target(&game_data, &StartingState::default(), &target, simulations, &mut rng);
+                    let pulls: Vec<f64> = records.iter().map(|record| record.pulls as f64).collect();
+                    let simulated = pulls.iter().sum::<f64>() / pulls.len() as f64;
+                    let variance = pulls.iter().map(|p| (p - simulated).powi(2)).sum::<f64>() / (pulls.len() - 1) as f64;
+                    let standard_error = (variance / pulls.len() as f64).sqrt();
+                    let exact = expected_pulls(&pulls_to_featured(definition, PityState::default()));
+
+                    assert!(
+                        (simulated - exact).abs() < 4.0 * standard_error,
+                        "{} {}: simulated {:.2} ± {:.2} but exact {:.2}", game_name, banner_name, simulated, standard_error, exact
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn arknights_rarity_counts_fill_the_pulls_before_the_six_star() {
+            for first_ten_guarantee in [true, false] {
+                let rates = ArknightsRates { first_ten_guarantee, ..ArknightsRates::default() };
+                let distribution = arknights_pulls_to_six_star(&rates);
+                assert_sums_to_one(&distribution.pmf);
+
+                // Every pull before the first 6-star is exactly one of the other rarities
+                let others = distribution.expected_five_star + distribution.expected_four_star + distribution.expected_three_star;
+                assert!((others - (expected_pulls(&distribution.pmf) - 1.0)).abs() < 1e-9);
+            }
+        }
+
+        #[test]
+        fn arknights_first_ten_guarantee_only_moves_lower_rarities() {
+            let with = arknights_pulls_to_six_star(&ArknightsRates::default());
+            let without = arknights_pulls_to_six_star(&ArknightsRates { first_ten_guarantee: false, ..ArknightsRates::default() });
+            assert_eq!(with.pmf, without.pmf);
+            assert!(with.expected_five_star > without.expected_five_star);
+            assert!(with.expected_four_star < without.expected_four_star);
+            assert!(with.expected_three_star < without.expected_three_star);
+        }
+    }
+}
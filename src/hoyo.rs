@@ -1,197 +1,754 @@
 pub mod hoyo {
+    use crate::banner::banner::{record, BannerModel, PityRates, PityState, PullOutcome, Rarity};
     use rand::Rng;
-    use rand::thread_rng;
+    use rand::RngCore;
     use lazy_static::lazy_static;
+    use serde::{Deserialize, Serialize};
+    use std::str::FromStr;
 
     lazy_static! {
-        static ref COMMON_FIVE_STAR_CHARACTER_CHANCE: f64 = 0.006;
-        static ref COMMON_SOFT_PITY_INCREMENT: f64 = 0.062;
-        static ref COMMON_CHARACTER_SOFT_PITY: i32 = 74;
-        static ref COMMON_CHARACTER_PITY: i32 = 90;
-        static ref COMMON_WEAPON_SOFT_PITY: i32 = 64;
-        static ref COMMON_WEAPON_PITY: i32 = 80;
-        static ref COMMON_FOUR_STAR_CHARACTER_CHANCE: f64 = 0.051;
-        static ref COMMON_FOUR_STAR_PITY: i32 = 10;
+        // Honkai: Star Rail character and light cone warps
+        static ref HSR: GameData = GameData {
+            character: BannerDefinition {
+                pity: PityRates {
+                    base_rate: 0.006,
+                    soft_pity: 74,
+                    soft_pity_increment: 0.062,
+                    hard_pity: 90,
+                    four_star_rate: 0.051,
+                    four_star_pity: 10,
+                    four_star_soft_pity: 8,
+                    four_star_soft_pity_increment: 0.51,
+                },
+                featured_chance: 0.5,
+                guarantee: true,
+                capturing_radiance: None,
+                featured_count: 1,
+                epitomized_path: None,
+                four_star_featured_chance: 0.5,
+                four_star_featured_count: 3,
+                four_star_character_share: 0.5,
+            },
+            weapon: BannerDefinition {
+                pity: PityRates {
+                    base_rate: 0.008,
+                    soft_pity: 64,
+                    soft_pity_increment: 0.062,
+                    hard_pity: 80,
+                    four_star_rate: 0.066,
+                    four_star_pity: 10,
+                    four_star_soft_pity: 8,
+                    four_star_soft_pity_increment: 0.51,
+                },
+                featured_chance: 0.75,
+                guarantee: true,
+                capturing_radiance: None,
+                featured_count: 1,
+                epitomized_path: None,
+                four_star_featured_chance: 0.75,
+                four_star_featured_count: 3,
+                four_star_character_share: 0.5,
+            },
+        };
+
+        // Genshin Impact character and weapon wishes; Capturing Radiance wins the 50/50 after
+        // three losses in a row and the weapon banner's Epitomized Path needs one fate point
+        static ref GENSHIN: GameData = GameData {
+            character: BannerDefinition {
+                pity: PityRates {
+                    base_rate: 0.006,
+                    soft_pity: 74,
+                    soft_pity_increment: 0.062,
+                    hard_pity: 90,
+                    four_star_rate: 0.051,
+                    four_star_pity: 10,
+                    four_star_soft_pity: 8,
+                    four_star_soft_pity_increment: 0.51,
+                },
+                featured_chance: 0.5,
+                guarantee: true,
+                capturing_radiance: Some(3),
+                featured_count: 1,
+                epitomized_path: None,
+                four_star_featured_chance: 0.5,
+                four_star_featured_count: 3,
+                four_star_character_share: 0.5,
+            },
+            weapon: BannerDefinition {
+                pity: PityRates {
+                    base_rate: 0.007,
+                    soft_pity: 63,
+                    soft_pity_increment: 0.062,
+                    hard_pity: 80,
+                    four_star_rate: 0.06,
+                    four_star_pity: 10,
+                    four_star_soft_pity: 8,
+                    four_star_soft_pity_increment: 0.51,
+                },
+                featured_chance: 0.75,
+                guarantee: true,
+                capturing_radiance: None,
+                featured_count: 2,
+                epitomized_path: Some(1),
+                four_star_featured_chance: 0.75,
+                four_star_featured_count: 5,
+                four_star_character_share: 0.5,
+            },
+        };
+
+        // Zenless Zone Zero agent and W-Engine channels
+        static ref ZZZ: GameData = GameData {
+            character: BannerDefinition {
+                pity: PityRates {
+                    base_rate: 0.006,
+                    soft_pity: 74,
+                    soft_pity_increment: 0.062,
+                    hard_pity: 90,
+                    four_star_rate: 0.094,
+                    four_star_pity: 10,
+                    four_star_soft_pity: 8,
+                    four_star_soft_pity_increment: 0.51,
+                },
+                featured_chance: 0.5,
+                guarantee: true,
+                capturing_radiance: None,
+                featured_count: 1,
+                epitomized_path: None,
+                four_star_featured_chance: 0.5,
+                four_star_featured_count: 2,
+                four_star_character_share: 0.5,
+            },
+            weapon: BannerDefinition {
+                pity: PityRates {
+                    base_rate: 0.01,
+                    soft_pity: 64,
+                    soft_pity_increment: 0.062,
+                    hard_pity: 80,
+                    four_star_rate: 0.15,
+                    four_star_pity: 10,
+                    four_star_soft_pity: 8,
+                    four_star_soft_pity_increment: 0.51,
+                },
+                featured_chance: 0.75,
+                guarantee: true,
+                capturing_radiance: None,
+                featured_count: 1,
+                epitomized_path: None,
+                four_star_featured_chance: 0.75,
+                four_star_featured_count: 2,
+                four_star_character_share: 0.5,
+            },
+        };
+    }
+
+    /// Rates and pity rules for a single HoYoverse banner
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct BannerDefinition {
+        #[serde(flatten)]
+        pub pity: PityRates,
+        /// Chance that a 5-star is the featured item (the 50/50 or 75/25)
+        pub featured_chance: f64,
+        /// Whether losing the featured roll guarantees the next 5-star
+        pub guarantee: bool,
+        /// Consecutive lost featured rolls after which the next one is won (Genshin's Capturing Radiance)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub capturing_radiance: Option<i32>,
+        /// Number of featured 5-stars sharing the featured chance equally; the first is the one pulled for
+        #[serde(default = "default_featured_count")]
+        pub featured_count: i32,
+        /// Fate points after which the next 5-star is the chosen featured item (Genshin's Epitomized Path)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub epitomized_path: Option<i32>,
+        /// Chance that a 4-star is one of the featured 4-stars; losing guarantees the next one
+        #[serde(default = "default_four_star_featured_chance")]
+        pub four_star_featured_chance: f64,
+        /// Number of featured 4-stars sharing the featured chance equally; the first is the one pulled for
+        #[serde(default = "default_four_star_featured_count")]
+        pub four_star_featured_count: i32,
+        /// Share of off-banner 4-stars that are characters rather than weapons
+        #[serde(default = "default_four_star_character_share")]
+        pub four_star_character_share: f64,
+    }
+
+    fn default_featured_count() -> i32 {
+        1
+    }
+
+    fn default_four_star_featured_chance() -> f64 {
+        0.5
+    }
+
+    fn default_four_star_featured_count() -> i32 {
+        3
+    }
+
+    fn default_four_star_character_share() -> f64 {
+        0.5
     }
 
+    impl BannerDefinition {
+        /// Checks that rates are probabilities and pity thresholds are consistent
+        pub fn validate(&self) -> Result<(), String> {
+            self.pity.validate()?;
+            for (name, rate) in [
+                ("featured_chance", self.featured_chance),
+                ("four_star_featured_chance", self.four_star_featured_chance),
+                ("four_star_character_share", self.four_star_character_share),
+            ] {
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(format!("{} must be between 0 and 1, got {}", name, rate));
+                }
+            }
+            if self.capturing_radiance.is_some_and(|losses| losses < 1) {
+                return Err("capturing_radiance must be at least 1".to_string());
+            }
+            if self.featured_count < 1 || self.four_star_featured_count < 1 {
+                return Err("featured_count and four_star_featured_count must be at least 1".to_string());
+            }
+            if self.epitomized_path.is_some_and(|points| points < 1) {
+                return Err("epitomized_path must be at least 1".to_string());
+            }
+            // Otherwise a simulated player would pull forever for the featured item
+            if self.featured_chance == 0.0 && !self.guarantee && self.capturing_radiance.is_none() && self.epitomized_path.is_none() {
+                return Err("featured_chance is 0 with no guarantee, so the featured item can never be obtained".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// Character and weapon banner definitions for one HoYoverse game
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
     pub struct GameData {
-        five_star_weapon_chance: f64,
-        limited_character_chance: f64,
-        limited_weapon_chance: f64,
+        pub character: BannerDefinition,
+        /// The weapon, light cone or W-Engine banner
+        pub weapon: BannerDefinition,
+    }
+
+    /// Built-in definitions for every supported HoYoverse game, in simulation order
+    pub fn builtin_games() -> Vec<(String, GameData)> {
+        vec![
+            ("hsr".to_string(), HSR.clone()),
+            ("genshin".to_string(), GENSHIN.clone()),
+            ("zzz".to_string(), ZZZ.clone()),
+        ]
     }
 
     impl GameData {
-        pub fn new(five_star_weapon_chance: f64, limited_character_chance: f64, limited_weapon_chance: f64) -> Self {
-            GameData {
-                five_star_weapon_chance,
-                limited_character_chance,
-                limited_weapon_chance,
+        /// Checks both banner definitions
+        pub fn validate(&self) -> Result<(), String> {
+            self.character.validate().map_err(|e| format!("character banner: {}", e))?;
+            self.weapon.validate().map_err(|e| format!("weapon banner: {}", e))
+        }
+    }
+
+    /// Pity and guarantee state a simulated player starts with
+    #[derive(Clone, Copy, Debug, Default, Serialize)]
+    pub struct StartingState {
+        pub character_pity: i32,
+        pub weapon_pity: i32,
+        /// Pulls since the last 4-star, applied to both banners
+        pub four_star_pity: i32,
+        pub character_guaranteed: bool,
+        pub weapon_guaranteed: bool,
+        /// Epitomized Path fate points on the weapon banner
+        pub fate_points: i32,
+    }
+
+    impl StartingState {
+        pub fn character_state(&self) -> PityState {
+            PityState {
+                pity: self.character_pity,
+                four_star_pity: self.four_star_pity,
+                guaranteed: self.character_guaranteed,
+                fate_points: 0,
+                four_star_guaranteed: false,
+            }
+        }
+
+        pub fn weapon_state(&self) -> PityState {
+            PityState {
+                pity: self.weapon_pity,
+                four_star_pity: self.four_star_pity,
+                guaranteed: self.weapon_guaranteed,
+                fate_points: self.fate_points,
+                four_star_guaranteed: false,
+            }
+        }
+
+        /// Checks that every counter is below the pity at which it would already have triggered
+        pub fn validate(&self, game_data: &GameData) -> Result<(), String> {
+            for (name, pity, definition) in [
+                ("character pity", self.character_pity, &game_data.character),
+                ("weapon pity", self.weapon_pity, &game_data.weapon),
+            ] {
+                if pity < 0 || pity >= definition.pity.hard_pity {
+                    return Err(format!("{} must be between 0 and {}, got {}", name, definition.pity.hard_pity - 1, pity));
+                }
+                if self.four_star_pity < 0 || self.four_star_pity >= definition.pity.four_star_pity {
+                    return Err(format!("4-star pity must be between 0 and {}, got {}", definition.pity.four_star_pity - 1, self.four_star_pity));
+                }
+            }
+            // Fate points are ignored on weapon banners without an Epitomized Path
+            let max_fate_points = game_data.weapon.epitomized_path.unwrap_or(i32::MAX);
+            if self.fate_points < 0 || self.fate_points > max_fate_points {
+                return Err(format!("fate points must be between 0 and {}, got {}", max_fate_points, self.fate_points));
+            }
+            Ok(())
+        }
+    }
+
+    /// A HoYoverse limited banner (character or weapon) with soft pity and a featured guarantee
+    pub struct HoyoBanner {
+        definition: BannerDefinition,
+        /// Whether the banner's featured items are weapons rather than characters
+        weapon_banner: bool,
+        state: PityState,
+        /// Featured rolls lost in a row; unlike pity this carries over between banners
+        lost_streak: i32,
+        /// Featured rolls (50/50s) decided so far, excluding guaranteed 5-stars
+        featured_rolls: i32,
+        /// Featured rolls won so far, including those won through Capturing Radiance
+        featured_wins: i32,
+        /// Featured rolls won through Capturing Radiance so far
+        radiance_wins: i32,
+    }
+
+    impl HoyoBanner {
+        /// Character banner, whose 5-stars are all characters
+        pub fn character(definition: &BannerDefinition) -> Self {
+            HoyoBanner::new(definition, false)
+        }
+
+        /// Weapon banner, whose 5-stars are all weapons
+        pub fn weapon(definition: &BannerDefinition) -> Self {
+            HoyoBanner::new(definition, true)
+        }
+
+        fn new(definition: &BannerDefinition, weapon_banner: bool) -> Self {
+            HoyoBanner {
+                definition: definition.clone(),
+                weapon_banner,
+                state: PityState::default(),
+                lost_streak: 0,
+                featured_rolls: 0,
+                featured_wins: 0,
+                radiance_wins: 0,
             }
         }
+
+        /// Returns how many featured rolls were decided, how many were won and how many of those
+        /// Capturing Radiance won
+        pub fn radiance_stats(&self) -> (i32, i32, i32) {
+            (self.featured_rolls, self.featured_wins, self.radiance_wins)
+        }
+
+        /// Resumes the banner from a saved pity state
+        pub fn set_state(&mut self, state: PityState) {
+            self.state = state;
+        }
+    }
+
+    impl BannerModel for HoyoBanner {
+        fn pull(&mut self, rng: &mut dyn RngCore) -> PullOutcome {
+            let definition = &self.definition;
+            self.state.pity += 1;
+
+            // HARD PITY CHECK - Guaranteed 5-star at exactly the pity value
+            let five_star_obtained = if self.state.pity == definition.pity.hard_pity {
+                true
+            }
+            // SOFT PITY & REGULAR ROLL CHECK
+            else {
+                rng.gen::<f64>() <= definition.pity.five_star_chance(self.state.pity)
+            };
+
+            if five_star_obtained {
+                // Epitomized Path: enough fate points make this 5-star the chosen item
+                if definition.epitomized_path.is_some_and(|points| self.state.fate_points >= points) {
+                    self.state.fate_points = 0;
+                    self.state.guaranteed = false;
+                    self.state.pity = 0;
+                    self.state.four_star_pity = 0;
+                    return PullOutcome::new(Rarity::Five, true).with_weapon(self.weapon_banner);
+                }
+
+                let featured = if self.state.guaranteed {
+                    true
+                } else {
+                    self.featured_rolls += 1;
+                    if definition.capturing_radiance.is_some_and(|losses| self.lost_streak >= losses) {
+                        // Capturing Radiance forces a win after enough consecutive losses
+                        self.featured_wins += 1;
+                        self.radiance_wins += 1;
+                        self.lost_streak = 0;
+                        true
+                    } else if rng.gen::<f64>() <= definition.featured_chance {
+                        self.featured_wins += 1;
+                        self.lost_streak = 0;
+                        true
+                    } else {
+                        self.lost_streak += 1;
+                        false
+                    }
+                };
+                self.state.guaranteed = !featured && definition.guarantee;
+                self.state.pity = 0;
+                // Reset 4-star pity when 5-star is obtained
+                self.state.four_star_pity = 0;
+
+                // The featured chance is shared equally between the featured items
+                let chosen = featured && (definition.featured_count == 1 || rng.gen_range(0..definition.featured_count) == 0);
+                if definition.epitomized_path.is_some() {
+                    self.state.fate_points = if chosen { 0 } else { self.state.fate_points + 1 };
+                }
+
+                let outcome = if featured && !chosen {
+                    PullOutcome::other_featured(Rarity::Five)
+                } else {
+                    PullOutcome::new(Rarity::Five, featured)
+                };
+                return outcome.with_weapon(self.weapon_banner);
+            }
+
+            // Handle 4-star pity if we didn't get a 5-star
+            self.state.four_star_pity += 1;
+            let four_star_chance = definition.pity.four_star_chance(self.state.four_star_pity);
+            if self.state.four_star_pity == definition.pity.four_star_pity || rng.gen::<f64>() <= four_star_chance {
+                self.state.four_star_pity = 0;
+
+                // 4-star featured roll, guaranteed after a loss
+                let featured = self.state.four_star_guaranteed || rng.gen::<f64>() <= definition.four_star_featured_chance;
+                self.state.four_star_guaranteed = !featured;
+
+                if featured {
+                    // Featured 4-stars match the banner type and share the featured chance equally
+                    let chosen = definition.four_star_featured_count == 1 || rng.gen_range(0..definition.four_star_featured_count) == 0;
+                    let outcome = if chosen {
+                        PullOutcome::new(Rarity::Four, true)
+                    } else {
+                        PullOutcome::other_featured(Rarity::Four)
+                    };
+                    return outcome.with_weapon(self.weapon_banner);
+                }
+
+                // Off-banner 4-stars can be either characters or weapons
+                let weapon = rng.gen::<f64>() >= definition.four_star_character_share;
+                return PullOutcome::new(Rarity::Four, false).with_weapon(weapon);
+            }
+
+            // If neither 5-star nor 4-star, it's a 3-star
+            PullOutcome::new(Rarity::Three, false)
+        }
+
+        fn pity(&self) -> PityState {
+            self.state
+        }
+
+        fn reset(&mut self) {
+            self.state = PityState::default();
+            self.lost_streak = 0;
+        }
+    }
+
+    /// Order in which a multi-copy target is pulled across the two banners
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum PullOrder {
+        CharacterFirst,
+        WeaponFirst,
+        /// Alternate banners after every featured copy, starting with the character
+        Interleaved,
+    }
+
+    impl FromStr for PullOrder {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "character-first" => Ok(PullOrder::CharacterFirst),
+                "weapon-first" => Ok(PullOrder::WeaponFirst),
+                "interleaved" => Ok(PullOrder::Interleaved),
+                _ => Err(format!("unknown pull order '{}', expected character-first, weapon-first or interleaved", s)),
+            }
+        }
+    }
+
+    /// Featured character and weapon copies a simulated player pulls for
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+    pub struct Target {
+        pub character_copies: i32,
+        pub weapon_copies: i32,
+        /// Copies of one specific featured 4-star character, pulled on the character banner
+        pub four_star_copies: i32,
+        pub order: PullOrder,
     }
 
-    pub fn h_simulate_game(game_data: &GameData, num_simulations: i32, pull_for_character: bool) -> Vec<(i32, i32, i32, i32, i32)> {
+    impl Target {
+        pub fn new(character_copies: i32, weapon_copies: i32, order: PullOrder) -> Self {
+            Target {
+                character_copies,
+                weapon_copies,
+                four_star_copies: 0,
+                order,
+            }
+        }
+
+        /// Parses a goal such as "C2 + R1", "E6 + S5" or "M0". Character levels (C, E or M) count
+        /// constellations, eidolons or mindscapes and need one more copy than the level; weapon
+        /// levels (R, S or P) count refinements, superimpositions or phases and need that many copies.
+        pub fn parse(spec: &str, order: PullOrder) -> Result<Self, String> {
+            Target::parse_levels(spec, order, &['C', 'E', 'M'], &['R', 'S', 'P'])
+        }
+
+        /// Parses a goal of '+'-separated levels, each a prefix letter and a number. Character
+        /// prefixes need one more copy than the level; weapon prefixes need that many copies.
+        pub fn parse_levels(spec: &str, order: PullOrder, character_prefixes: &[char], weapon_prefixes: &[char]) -> Result<Self, String> {
+            let mut target = Target::new(0, 0, order);
+            let invalid = |part: &str| format!("invalid target '{}', expected e.g. {}2 or {}1", part, character_prefixes[0], weapon_prefixes[0]);
+
+            for part in spec.split('+').map(str::trim) {
+                let mut chars = part.chars();
+                let prefix = chars.next().map(|c| c.to_ascii_uppercase());
+                let level: i32 = chars.as_str().parse().map_err(|_| invalid(part))?;
+
+                match prefix {
+                    Some(prefix) if character_prefixes.contains(&prefix) && level >= 0 => target.character_copies = level + 1,
+                    Some(prefix) if weapon_prefixes.contains(&prefix) && level >= 1 => target.weapon_copies = level,
+                    _ => return Err(invalid(part)),
+                }
+            }
+
+            Ok(target)
+        }
+
+        /// Whether the next pull goes to the character banner, or `None` once the target is complete
+        pub fn next_is_character(&self, character_successes: i32, weapon_successes: i32, four_star_successes: i32) -> Option<bool> {
+            let need_character = character_successes < self.character_copies || four_star_successes < self.four_star_copies;
+            let need_weapon = weapon_successes < self.weapon_copies;
+
+            match (need_character, need_weapon) {
+                (false, false) => None,
+                (true, false) => Some(true),
+                (false, true) => Some(false),
+                (true, true) => Some(match self.order {
+                    PullOrder::CharacterFirst => true,
+                    PullOrder::WeaponFirst => false,
+                    PullOrder::Interleaved => character_successes <= weapon_successes,
+                }),
+            }
+        }
+    }
+
+    record! {
+        /// One player's pulls on a HoYoverse banner or multi-copy target
+        pub struct HoyoRecord("hoyo") {
+            pulls: "Pulls" => "Total pulls until the target was complete",
+            limited: "Limited" => "Chosen featured 5-star characters",
+            weapon: "Weapon" => "Chosen featured 5-star weapons",
+            four_star: "FourStar" => "4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            featured_four_star: "FeaturedFourStar" => "Featured 4-stars received",
+            off_banner_four_star: "OffBannerFourStar" => "Off-banner 4-stars received",
+            four_star_character: "FourStarCharacter" => "4-star characters received",
+            four_star_weapon: "FourStarWeapon" => "4-star weapons received",
+            target_four_star: "TargetFourStar" => "Copies of the targeted featured 4-star character",
+            other_featured: "OtherFeatured" => "Featured 5-stars other than the chosen one",
+            standard_character: "StandardCharacter" => "Standard 5-star characters from lost featured rolls",
+            standard_weapon: "StandardWeapon" => "Standard 5-star weapons from lost featured rolls",
+        }
+    }
+
+    /// Simulates players pulling until the target is complete. The 5-star, 4-star and 3-star
+    /// counts of each record add up to its total pulls.
+    /// Pity restarts from `start` for every record, while the Capturing Radiance streak carries
+    /// over from record to record as if each were the same player's next banner.
+    pub fn h_simulate_target(game_data: &GameData, start: &StartingState, target: &Target, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<HoyoRecord> {
         let mut results = Vec::with_capacity(num_simulations as usize);
+        let mut character_banner = HoyoBanner::character(&game_data.character);
+        let mut weapon_banner = HoyoBanner::weapon(&game_data.weapon);
 
         for _ in 0..num_simulations {
-            let mut pulls = 0;
-            let mut five_char_successes = 0;
-            let mut four_char_success = 0;
-            let mut three_char_success = 0;
-            let mut weapon_successes = 0;
-            let mut limited_successes = 0;
-            let mut curr_weapon_pity = 0;
-            let mut curr_char_pity = 0;
-            let mut curr_weapon_guaranteed = false;
-            let mut curr_character_guaranteed = false;
-            let mut curr_four_star_pity = 0;
-
-            // Constants lookup once outside the loop
-            let curr_five_star_chance = *COMMON_FIVE_STAR_CHARACTER_CHANCE;
-            let curr_four_star_chance = *COMMON_FOUR_STAR_CHARACTER_CHANCE;
-            let curr_five_star_weapon_chance = game_data.five_star_weapon_chance;
-            let curr_soft_pity_increment = *COMMON_SOFT_PITY_INCREMENT;
-            let curr_character_soft_pity = *COMMON_CHARACTER_SOFT_PITY;
-            let curr_character_pity = *COMMON_CHARACTER_PITY;
-            let four_star_pity = *COMMON_FOUR_STAR_PITY;
-            let curr_weapon_soft_pity = *COMMON_WEAPON_SOFT_PITY;
-            let curr_weapon_pity_value = *COMMON_WEAPON_PITY;
-            let curr_limited_weapon_chance = game_data.limited_weapon_chance;
-            let curr_limited_character_chance = game_data.limited_character_chance;
-
-            'pull_loop: loop {
-                pulls += 1;
-                let mut five_star_obtained = false;
-                let mut item_obtained = false;
-
-                if pull_for_character || limited_successes < 7 {
-                    // Character banner logic
-                    curr_char_pity += 1;
-
-                    // HARD PITY CHECK - Guaranteed 5-star at exactly 90 pulls
-                    if curr_char_pity == curr_character_pity {
-                        // We've reached hard pity - guaranteed 5-star
-                        if curr_character_guaranteed || thread_rng().gen::<f64>() <= curr_limited_character_chance {
-                            five_char_successes += 1;
-                            limited_successes += 1;
-                            curr_character_guaranteed = false;
-                        } else {
-                            curr_character_guaranteed = true;
-                        }
-                        curr_char_pity = 0;
-                        five_star_obtained = true;
-                        item_obtained = true;
+            let mut record = HoyoRecord::default();
 
-                        if pull_for_character {
-                            break 'pull_loop; // Stop if we're targeting character and got it
-                        }
-                    }
-                    // SOFT PITY & REGULAR ROLL CHECK
-                    else {
-                        let mut chance = curr_five_star_chance;
-                        // Apply soft pity if applicable
-                        if curr_char_pity > curr_character_soft_pity {
-                            chance += curr_soft_pity_increment * (curr_char_pity - curr_character_soft_pity) as f64;
-                        }
+            character_banner.set_state(start.character_state());
+            weapon_banner.set_state(start.weapon_state());
 
-                        // Check if we get a 5-star based on the probability
-                        if thread_rng().gen::<f64>() <= chance {
-                            // Got a 5-star before hard pity
-                            if curr_character_guaranteed || thread_rng().gen::<f64>() <= curr_limited_character_chance {
-                                five_char_successes += 1;
-                                limited_successes += 1;
-                                curr_character_guaranteed = false;
-                            } else {
-                                curr_character_guaranteed = true;
-                            }
-                            curr_char_pity = 0;
-                            five_star_obtained = true;
-                            item_obtained = true;
+            while let Some(on_character_banner) = target.next_is_character(record.limited, record.weapon, record.target_four_star) {
+                record.pulls += 1;
 
-                            if pull_for_character {
-                                break 'pull_loop; // Stop if we're targeting character and got it
+                let outcome = if on_character_banner {
+                    character_banner.pull(rng)
+                } else {
+                    weapon_banner.pull(rng)
+                };
+
+                match outcome.rarity {
+                    Rarity::Five | Rarity::Six => {
+                        if outcome.chosen {
+                            if on_character_banner {
+                                record.limited += 1;
+                            } else {
+                                record.weapon += 1;
                             }
+                        } else if outcome.featured {
+                            record.other_featured += 1;
+                        } else if outcome.weapon {
+                            record.standard_weapon += 1;
+                        } else {
+                            record.standard_character += 1;
                         }
                     }
-                } else {
-                    // Weapon banner logic
-                    curr_weapon_pity += 1;
-
-                    // HARD PITY CHECK - Guaranteed 5-star at exactly 80 pulls
-                    if curr_weapon_pity == curr_weapon_pity_value {
-                        // We've reached hard pity - guaranteed 5-star weapon
-                        if curr_weapon_guaranteed || thread_rng().gen::<f64>() <= curr_limited_weapon_chance {
-                            weapon_successes += 1;
-                            curr_weapon_guaranteed = false;
+                    Rarity::Four => {
+                        record.four_star += 1;
+                        if outcome.featured {
+                            record.featured_four_star += 1;
                         } else {
-                            curr_weapon_guaranteed = true;
+                            record.off_banner_four_star += 1;
                         }
-                        curr_weapon_pity = 0;
-                        five_star_obtained = true;
-                        item_obtained = true;
-
-                        if !pull_for_character {
-                            break 'pull_loop; // Stop if we're targeting weapon and got it
+                        if outcome.weapon {
+                            record.four_star_weapon += 1;
+                        } else {
+                            record.four_star_character += 1;
                         }
-                    }
-                    // SOFT PITY & REGULAR ROLL CHECK
-                    else {
-                        let mut chance = curr_five_star_weapon_chance;
-                        // Apply soft pity if applicable
-                        if curr_weapon_pity > curr_weapon_soft_pity {
-                            chance += curr_soft_pity_increment * (curr_weapon_pity - curr_weapon_soft_pity) as f64;
-                        }
-
-                        // Check if we get a 5-star based on the probability
-                        if thread_rng().gen::<f64>() <= chance {
-                            // Got a 5-star before hard pity
-                            if curr_weapon_guaranteed || thread_rng().gen::<f64>() <= curr_limited_weapon_chance {
-                                weapon_successes += 1;
-                                curr_weapon_guaranteed = false;
-                            } else {
-                                curr_weapon_guaranteed = true;
-                            }
-                            curr_weapon_pity = 0;
-                            five_star_obtained = true;
-                            item_obtained = true;
-
-                            if !pull_for_character {
-                                break 'pull_loop; // Stop if we're targeting weapon and got it
-                            }
+                        if outcome.chosen && on_character_banner {
+                            record.target_four_star += 1;
                         }
                     }
+                    Rarity::Three => record.three_star += 1,
                 }
+            }
 
-                // Handle 4-star pity if we didn't get a 5-star
-                if !five_star_obtained {
-                    curr_four_star_pity += 1;
+            debug_assert_eq!(
+                record.limited + record.weapon + record.other_featured + record.standard_character + record.standard_weapon
+                    + record.four_star + record.three_star,
+                record.pulls
+            );
+            results.push(record);
+        }
 
-                    // Check for guaranteed 4-star at pity
-                    if curr_four_star_pity == four_star_pity {
-                        four_char_success += 1;
-                        curr_four_star_pity = 0;
-                        item_obtained = true;
-                    }
-                    // Regular chance for 4-star
-                    else if thread_rng().gen::<f64>() <= curr_four_star_chance {
-                        four_char_success += 1;
-                        curr_four_star_pity = 0;
-                        item_obtained = true;
+        results
+    }
+
+    /// Counts how many simulated players complete the target without spending more than `budget` pulls
+    pub fn h_simulate_budget(game_data: &GameData, start: &StartingState, target: &Target, budget: i32, num_simulations: i32, rng: &mut dyn RngCore) -> i32 {
+        let mut character_banner = HoyoBanner::character(&game_data.character);
+        let mut weapon_banner = HoyoBanner::weapon(&game_data.weapon);
+        let mut successes = 0;
+
+        for _ in 0..num_simulations {
+            let mut character_successes = 0;
+            let mut weapon_successes = 0;
+            let mut four_star_successes = 0;
+            let mut pulls = 0;
+
+            character_banner.set_state(start.character_state());
+            weapon_banner.set_state(start.weapon_state());
+
+            while let Some(on_character_banner) = target.next_is_character(character_successes, weapon_successes, four_star_successes) {
+                if pulls == budget {
+                    break;
+                }
+                pulls += 1;
+
+                if on_character_banner {
+                    let outcome = character_banner.pull(rng);
+                    match outcome.rarity {
+                        Rarity::Five if outcome.chosen => character_successes += 1,
+                        Rarity::Four if outcome.chosen => four_star_successes += 1,
+                        _ => {}
                     }
                 } else {
-                    // Reset 4-star pity when 5-star is obtained
-                    curr_four_star_pity = 0;
+                    let outcome = weapon_banner.pull(rng);
+                    if outcome.rarity == Rarity::Five && outcome.chosen {
+                        weapon_successes += 1;
+                    }
+                }
+            }
+
+            if target.next_is_character(character_successes, weapon_successes, four_star_successes).is_none() {
+                successes += 1;
+            }
+        }
+
+        successes
+    }
+
+    /// Simulates one player pulling each of `num_banners` consecutive character banners until its
+    /// featured character, with the Capturing Radiance streak carrying over between banners.
+    /// Returns the number of featured rolls decided, how many were won and how many of them
+    /// Capturing Radiance won.
+    pub fn h_simulate_radiance(game_data: &GameData, num_banners: i32, rng: &mut dyn RngCore) -> (i32, i32, i32) {
+        let mut banner = HoyoBanner::character(&game_data.character);
+
+        for _ in 0..num_banners {
+            loop {
+                let outcome = banner.pull(rng);
+                if outcome.rarity == Rarity::Five && outcome.chosen {
+                    break;
                 }
+            }
+        }
+
+        banner.radiance_stats()
+    }
 
-                // If neither 5-star nor 4-star, it's a 3-star
-                if !item_obtained {
-                    three_char_success += 1;
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::banner::banner::SimRng;
+        use rand::SeedableRng;
+
+        #[test]
+        fn rarity_counts_add_up_to_pulls() {
+            let mut rng = SimRng::seed_from_u64(1);
+            let targets = [
+                Target::new(1, 0, PullOrder::CharacterFirst),
+                Target::new(0, 1, PullOrder::WeaponFirst),
+                Target { four_star_copies: 2, ..Target::new(2, 1, PullOrder::Interleaved) },
+            ];
+            for (_, game_data) in builtin_games() {
+                for target in &targets {
+                    for record in h_simulate_target(&game_data, &StartingState::default(), target, 500, &mut rng) {
+                        let five_stars = record.limited + record.weapon + record.other_featured + record.standard_character + record.standard_weapon;
+                        assert_eq!(five_stars + record.four_star + record.three_star, record.pulls, "{:?}", record);
+                    }
                 }
             }
+        }
+
+        #[test]
+        fn target_levels_count_copies() {
+            let target = Target::parse("C2 + R1", PullOrder::CharacterFirst).unwrap();
+            assert_eq!((target.character_copies, target.weapon_copies), (3, 1));
+
+            let target = Target::parse("e6+s5", PullOrder::WeaponFirst).unwrap();
+            assert_eq!((target.character_copies, target.weapon_copies), (7, 5));
+            assert_eq!(target.order, PullOrder::WeaponFirst);
 
-            results.push((pulls, limited_successes, weapon_successes, four_char_success, three_char_success));
+            let target = Target::parse("M0", PullOrder::Interleaved).unwrap();
+            assert_eq!(target, Target::new(1, 0, PullOrder::Interleaved));
+
+            let target = Target::parse("P3", PullOrder::CharacterFirst).unwrap();
+            assert_eq!(target, Target::new(0, 3, PullOrder::CharacterFirst));
         }
 
-        results
+        #[test]
+        fn target_rejects_malformed_levels() {
+            for spec in ["", "C", "X2", "R0", "C-1", "C2 + ", "C2 R1"] {
+                assert!(Target::parse(spec, PullOrder::CharacterFirst).is_err(), "accepted '{}'", spec);
+            }
+        }
+
+        #[test]
+        fn target_prefixes_are_per_game() {
+            let target = Target::parse_levels("S2 + R1", PullOrder::CharacterFirst, &['S'], &['R']).unwrap();
+            assert_eq!(target, Target::new(3, 1, PullOrder::CharacterFirst));
+
+            // HoYoverse character levels are not Wuthering Waves levels
+            let error = Target::parse_levels("C2", PullOrder::CharacterFirst, &['S'], &['R']).unwrap_err();
+            assert_eq!(error, "invalid target 'C2', expected e.g. S2 or R1");
+        }
     }
 }
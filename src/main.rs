@@ -1,225 +1,951 @@
+#![allow(clippy::module_inception)]
+
+mod banner;
+mod config;
+mod exact;
 mod wuwa;
 mod arknights;
 mod hoyo;
+mod standard;
 
-use crate::hoyo::hoyo::{h_simulate_game, GameData};
-use crate::arknights::arknights::a_simulate_game;
-use crate::wuwa::wuwa::w_simulate_game;
-use std::fs::{self, File};
+use crate::banner::banner::{chunk_rng, simulate_four_star_gaps, SimRng, Column, FourStarGapRecord, Record};
+use crate::config::config::BannerConfig;
+use crate::exact::exact::{arknights_pulls_to_six_star, expected_pulls, pulls_to_featured};
+use crate::hoyo::hoyo::{h_simulate_budget, h_simulate_radiance, h_simulate_target, BannerDefinition, GameData, HoyoBanner, HoyoRecord, PullOrder, StartingState, Target};
+use crate::arknights::arknights::{a_simulate_game, a_simulate_headhunting, a_simulate_sequence, parse_sequence, ArknightsRecord, Currency, HeadhuntingRecord, Purchase, SequenceRecord, SequenceStep, Spending};
+use crate::standard::standard::{simulate_standard, StandardRecord};
+use crate::wuwa::wuwa::{parse_target as parse_wuwa_target, w_simulate_game, w_simulate_target, WuwaBanner, WuwaRecord, WuwaTargetRecord};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write, BufWriter};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use rand::{Rng, RngCore};
 use rayon::prelude::*;
-use clap::{Command, Arg};
+use serde::Serialize;
+use serde_json::{json, Value};
+use arrow_array::{ArrayRef, Int32Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use indicatif::{ProgressBar, ProgressStyle, MultiProgress};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+
+/// File format of the simulation output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Comma-separated values with a header row of column names
+    Csv,
+    /// JSON Lines, one object per record keyed by column name
+    Json,
+    /// Zstandard-compressed Parquet with one 32-bit integer column per record column
+    Parquet,
+}
 
-/// Creates a CSV writer with header
-fn create_csv_writer(filepath: &str, header: &str) -> io::Result<BufWriter<File>> {
-    // Ensure directory exists
-    let path = Path::new(filepath);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "jsonl",
+            OutputFormat::Parquet => "parquet",
+        }
     }
+}
 
-    let file = File::create(filepath)?;
-    let mut writer = BufWriter::new(file);
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(format!("unknown format '{}', expected csv, json or parquet", s)),
+        }
+    }
+}
 
-    // Write header
-    writer.write_all(header.as_bytes())?;
-    writer.write_all(b"\n")?;
+/// Buffered destination of one output: a file, or stdout when writing to `-`
+type OutputWriter = BufWriter<Box<dyn Write + Send>>;
 
-    Ok(writer)
+/// Where output files are written and how they are named
+struct Output {
+    /// Directory holding every output file, or None to write the single run to stdout
+    dir: Option<PathBuf>,
+    /// File name relative to the directory, without the extension
+    template: String,
+    format: OutputFormat,
+    seed: u64,
+    /// Start time of the run, substituted for `{timestamp}`
+    timestamp: String,
+    /// Refuse to overwrite existing files
+    no_clobber: bool,
 }
 
-/// Writes a chunk of data to CSV
-fn write_chunk_to_csv(writer: &mut BufWriter<File>, chunk: &[(i32, i32, i32, i32, i32)]) -> io::Result<()> {
-    for (p1, p2, p3, p4, p5) in chunk {
-        writer.write_all(format!("{},{},{},{},{}\n", p1, p2, p3, p4, p5).as_bytes())?;
+impl Output {
+    /// Checks that the file name template only uses known placeholders
+    fn validate(&self) -> Result<(), String> {
+        let rendered = self.render("game", "banner");
+        if rendered.contains('{') || rendered.contains('}') {
+            return Err(format!(
+                "unknown placeholder in file name '{}', expected {{game}}, {{banner}}, {{seed}} or {{timestamp}}",
+                self.template
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the file name template gives every output file of a run its own path, so no
+    /// file overwrites another one written by the same run
+    fn check_distinct<'a>(&self, files: impl IntoIterator<Item = (&'a str, &'a str)>) -> io::Result<()> {
+        if self.dir.is_none() {
+            return Ok(());
+        }
+
+        let mut seen: BTreeMap<String, (&str, &str)> = BTreeMap::new();
+        for (game, banner) in files {
+            if let Some((other_game, other_banner)) = seen.insert(self.render(game, banner), (game, banner)) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "file name '{}' gives {} {} and {} {} the same path; add {{game}} or {{banner}} to it",
+                        self.template, other_game, other_banner, game, banner
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// With `--no-clobber`, checks that none of the files a run writes exists yet, so nothing is
+    /// simulated only to fail when its file is opened. Each file is given by game, banner and
+    /// extension and comes with its metadata sidecar; a matching `schema.json` is kept as it is.
+    fn check_clobber<'a>(&self, files: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>) -> io::Result<()> {
+        let Some(dir) = self.dir.as_ref().filter(|_| self.no_clobber) else {
+            return Ok(());
+        };
+
+        let schema = dir.join("schema.json");
+        if fs::read_to_string(&schema).is_ok_and(|existing| existing != schema_contents()) {
+            return Err(clobber_error(&schema));
+        }
+        for (game, banner, extension) in files {
+            for path in [self.path(game, banner, extension), self.metadata_path(game, banner, extension)].into_iter().flatten() {
+                if path.exists() {
+                    return Err(clobber_error(&path));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, game: &str, banner: &str) -> String {
+        self.template
+            .replace("{game}", game)
+            .replace("{banner}", banner)
+            .replace("{seed}", &self.seed.to_string())
+            .replace("{timestamp}", &self.timestamp)
+    }
+
+    /// Path of a banner's output file, or None when writing to stdout
+    fn path(&self, game: &str, banner: &str, extension: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.{}", self.render(game, banner), extension)))
+    }
+
+    /// Path of the metadata sidecar of a banner's output file, named after the full file name so
+    /// files of the same banner in different formats keep separate sidecars
+    fn metadata_path(&self, game: &str, banner: &str, extension: &str) -> Option<PathBuf> {
+        self.path(game, banner, &format!("{}.meta.json", extension))
+    }
+
+    /// Creates a file and its directory; with `--no-clobber` an existing file is an error
+    fn create(&self, path: &Path) -> io::Result<OutputWriter> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = if self.no_clobber {
+            OpenOptions::new().write(true).create_new(true).open(path).map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => clobber_error(path),
+                _ => e,
+            })?
+        } else {
+            File::create(path)?
+        };
+        Ok(BufWriter::new(Box::new(file)))
+    }
+
+    /// Opens a run's output in the selected format. Also returns where it is written to.
+    fn create_run(&self, run: &BannerRun) -> io::Result<(String, RowWriter)> {
+        let (name, writer) = match self.path(&run.game, &run.banner, self.format.extension()) {
+            Some(path) => (path.display().to_string(), self.create(&path)?),
+            None => ("stdout".to_string(), BufWriter::new(Box::new(io::stdout()) as Box<dyn Write + Send>)),
+        };
+        Ok((name, RowWriter::new(writer, self.format, run.record, run.columns)?))
+    }
+
+    /// Writes a file's metadata next to it; nothing is written for stdout
+    fn write_metadata(&self, game: &str, banner: &str, extension: &str, metadata: &Metadata) -> io::Result<()> {
+        let Some(path) = self.metadata_path(game, banner, extension) else {
+            return Ok(());
+        };
+        let mut writer = self.create(&path)?;
+        serde_json::to_writer_pretty(&mut writer, metadata)?;
+        writer.write_all(b"\n")?;
+        writer.flush()
+    }
+
+    /// Writes the columns of every record type, keyed by record name, for the analysis loaders.
+    /// With `--no-clobber` an existing schema is kept as long as it matches.
+    fn write_schema(&self) -> io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let contents = schema_contents();
+        let path = dir.join("schema.json");
+        if self.no_clobber && fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+            return Ok(());
+        }
+        let mut writer = self.create(&path)?;
+        writer.write_all(contents.as_bytes())?;
+        writer.flush()
+    }
+}
+
+/// Error for an existing file that `--no-clobber` keeps from being overwritten
+fn clobber_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists and --no-clobber is set", path.display()),
+    )
+}
+
+/// Contents of `schema.json`: the columns of every record type, keyed by record name
+fn schema_contents() -> String {
+    let schema: BTreeMap<&str, &[Column]> = BTreeMap::from([
+        (HoyoRecord::NAME, HoyoRecord::COLUMNS),
+        (FourStarGapRecord::NAME, FourStarGapRecord::COLUMNS),
+        (WuwaRecord::NAME, WuwaRecord::COLUMNS),
+        (WuwaTargetRecord::NAME, WuwaTargetRecord::COLUMNS),
+        (ArknightsRecord::NAME, ArknightsRecord::COLUMNS),
+        (HeadhuntingRecord::NAME, HeadhuntingRecord::COLUMNS),
+        (SequenceRecord::NAME, SequenceRecord::COLUMNS),
+        (StandardRecord::NAME, StandardRecord::COLUMNS),
+        (EXACT_RECORD, EXACT_COLUMNS),
+    ]);
+    serde_json::to_string_pretty(&schema).expect("schema serializes to JSON") + "\n"
+}
+
+/// Results of a chunk of simulations: each simulation's record values in column order, one row
+/// after another
+type ResultRows = Vec<i32>;
+
+/// Writes a run's rows in the selected format
+enum RowWriter {
+    /// CSV or JSON Lines, written row by row
+    Text {
+        writer: OutputWriter,
+        format: OutputFormat,
+        columns: &'static [Column],
+    },
+    /// Parquet, written one record batch per chunk
+    Parquet {
+        writer: Box<ArrowWriter<OutputWriter>>,
+        schema: SchemaRef,
+    },
+}
+
+impl RowWriter {
+    /// Starts the output, writing the CSV header or the Parquet schema
+    fn new(mut writer: OutputWriter, format: OutputFormat, record: &str, columns: &'static [Column]) -> io::Result<Self> {
+        match format {
+            OutputFormat::Csv | OutputFormat::Json => {
+                if format == OutputFormat::Csv {
+                    let header: Vec<&str> = columns.iter().map(|column| column.name).collect();
+                    writeln!(writer, "{}", header.join(","))?;
+                }
+                Ok(RowWriter::Text { writer, format, columns })
+            }
+            OutputFormat::Parquet => {
+                let fields: Vec<Field> = columns.iter()
+                    .map(|column| {
+                        Field::new(column.name, DataType::Int32, false)
+                            .with_metadata(HashMap::from([("description".to_string(), column.description.to_string())]))
+                    })
+                    .collect();
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .set_key_value_metadata(Some(vec![KeyValue::new("record".to_string(), record.to_string())]))
+                    .build();
+                let schema = Arc::new(Schema::new(fields));
+                let writer = ArrowWriter::try_new(writer, schema.clone(), Some(properties))
+                    .map_err(io::Error::other)?;
+                Ok(RowWriter::Parquet { writer: Box::new(writer), schema })
+            }
+        }
+    }
+
+    /// Writes a chunk of rows. Text fields go straight into the buffered writer.
+    fn write(&mut self, rows: &[i32]) -> io::Result<()> {
+        match self {
+            RowWriter::Text { writer, format, columns } => {
+                let json = *format == OutputFormat::Json;
+                for row in rows.chunks_exact(columns.len()) {
+                    if json {
+                        writer.write_all(b"{")?;
+                    }
+                    for (index, (column, value)) in columns.iter().zip(row).enumerate() {
+                        if index > 0 {
+                            writer.write_all(b",")?;
+                        }
+                        if json {
+                            write!(writer, "\"{}\":", column.name)?;
+                        }
+                        write!(writer, "{}", value)?;
+                    }
+                    writer.write_all(if json { b"}\n" } else { b"\n" })?;
+                }
+                Ok(())
+            }
+            RowWriter::Parquet { writer, schema } => {
+                if rows.is_empty() {
+                    return Ok(());
+                }
+                let width = schema.fields().len();
+                let arrays: Vec<ArrayRef> = (0..width)
+                    .map(|index| Arc::new(Int32Array::from_iter_values(rows.iter().skip(index).step_by(width).copied())) as ArrayRef)
+                    .collect();
+                let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(io::Error::other)?;
+                writer.write(&batch).map_err(io::Error::other)
+            }
+        }
+    }
+
+    /// Flushes the rows and, for Parquet, writes the file footer
+    fn finish(self) -> io::Result<()> {
+        match self {
+            RowWriter::Text { mut writer, .. } => writer.flush(),
+            RowWriter::Parquet { writer, .. } => {
+                let mut writer = writer.into_inner().map_err(io::Error::other)?;
+                writer.flush()
+            }
+        }
+    }
+}
+
+/// Record name of the exact distributions in the schema and metadata
+const EXACT_RECORD: &str = "exact";
+
+/// Columns of the exact distributions, which hold probabilities rather than counts
+const EXACT_COLUMNS: &[Column] = &[
+    Column { name: "Pulls", description: "Pull on which the featured item is obtained" },
+    Column { name: "Probability", description: "Probability of obtaining it on exactly this pull" },
+    Column { name: "Cumulative", description: "Probability of obtaining it by this pull" },
+];
+
+/// Provenance of one output file, written next to it as `<name>.<extension>.meta.json`
+#[derive(Serialize)]
+struct Metadata<'a> {
+    engine: &'static str,
+    engine_version: &'static str,
+    game: &'a str,
+    banner: &'a str,
+    /// Where the results were written
+    output: &'a str,
+    /// Record name in schema.json, repeated with its columns so the file stands alone
+    record: &'static str,
+    columns: &'a [Column],
+    /// Game rules and player state the results were produced with
+    parameters: &'a Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    simulations: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunk_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threads: Option<usize>,
+    started: String,
+    wall_time_seconds: f64,
+}
+
+impl<'a> Metadata<'a> {
+    /// Metadata for results computed without sampling; the caller adds the run settings of simulations
+    fn new(game: &'a str, banner: &'a str, output: &'a str, record: &'static str, columns: &'a [Column], parameters: &'a Value, started: DateTime<Utc>) -> Self {
+        Metadata {
+            engine: env!("CARGO_PKG_NAME"),
+            engine_version: env!("CARGO_PKG_VERSION"),
+            game,
+            banner,
+            output,
+            record,
+            columns,
+            parameters,
+            seed: None,
+            simulations: None,
+            chunk_size: None,
+            threads: None,
+            started: started.to_rfc3339_opts(SecondsFormat::Millis, true),
+            wall_time_seconds: (Utc::now() - started).as_seconds_f64(),
+        }
+    }
+}
+
+/// Writes an exact pull-count distribution with its cumulative probabilities, and its metadata
+fn write_exact_csv(output: &Output, game_name: &str, banner_name: &str, parameters: &Value, started: DateTime<Utc>, pmf: &[f64]) -> io::Result<()> {
+    let banner_name = format!("{}_exact", banner_name);
+    let Some(path) = output.path(game_name, &banner_name, "csv") else {
+        return Ok(());
+    };
+    let mut writer = output.create(&path)?;
+    let header: Vec<&str> = EXACT_COLUMNS.iter().map(|column| column.name).collect();
+    writeln!(writer, "{}", header.join(","))?;
+    let mut cumulative = 0.0;
+    for (i, probability) in pmf.iter().enumerate() {
+        cumulative += probability;
+        writeln!(writer, "{},{},{}", i + 1, probability, cumulative)?;
+    }
+    writer.flush()?;
+
+    let destination = path.display().to_string();
+    output.write_metadata(game_name, &banner_name, "csv", &Metadata::new(game_name, &banner_name, &destination, EXACT_RECORD, EXACT_COLUMNS, parameters, started))
+}
+
+/// Game and banner names of the exact distribution files a default run writes for the selection
+fn exact_files(config: &BannerConfig, selection: &Selection) -> Vec<(String, String)> {
+    let mut files: Vec<(String, String)> = config.hoyo.iter()
+        .flat_map(|(game_name, _)| ["character", "weapon"].map(|banner_name| (game_name.clone(), banner_name)))
+        .filter(|(game_name, banner_name)| selection.includes(game_name, banner_name))
+        .map(|(game_name, banner_name)| (game_name, format!("{}_exact", banner_name)))
+        .collect();
+    if selection.includes("arknights", "arknights") {
+        files.push(("arknights".to_string(), "arknights_exact".to_string()));
+    }
+    files
+}
+
+/// Solves every HoYoverse banner exactly and writes the distributions next to the simulation output
+fn solve_hoyo_exact(config: &BannerConfig, start: &StartingState, selection: &Selection, output: &Output) -> io::Result<()> {
+    for (game_name, game_data) in &config.hoyo {
+        for (banner_name, definition, state) in [
+            ("character", &game_data.character, start.character_state()),
+            ("weapon", &game_data.weapon, start.weapon_state()),
+        ] {
+            if !selection.includes(game_name, banner_name) {
+                continue;
+            }
+            let started = Utc::now();
+            let pmf = pulls_to_featured(definition, state);
+            let parameters = json!({ "definition": definition, "start": start });
+            write_exact_csv(output, game_name, banner_name, &parameters, started, &pmf)?;
+            println!("{} {}: exactly {:.3} expected pulls to the featured 5-star", game_name, banner_name, expected_pulls(&pmf));
+        }
     }
     Ok(())
 }
 
-/// Simulates HoYoverse games with streaming CSV output
-fn simulate_hoyo_games(num_simulations: u64, chunk_size: u64) -> io::Result<()> {
-    let games = [
-        ("hsr", GameData::new(0.008, 0.5, 0.75)),
-        ("genshin", GameData::new(0.007, 0.55, 0.75)),
-        ("zzz", GameData::new(0.01, 0.5, 0.75)),
-    ];
+/// Solves the Arknights soft pity model exactly and writes the distribution next to the simulation output
+fn solve_arknights_exact(config: &BannerConfig, output: &Output) -> io::Result<()> {
+    let started = Utc::now();
+    let distribution = arknights_pulls_to_six_star(&config.arknights);
+    let parameters = json!({ "rates": config.arknights });
+    write_exact_csv(output, "arknights", "arknights", &parameters, started, &distribution.pmf)?;
+    println!(
+        "arknights: exactly {:.3} expected pulls to a 6-star, with {:.3} 5-stars, {:.3} 4-stars and {:.3} 3-stars on the way",
+        expected_pulls(&distribution.pmf),
+        distribution.expected_five_star,
+        distribution.expected_four_star,
+        distribution.expected_three_star
+    );
+    Ok(())
+}
 
-    let multi_progress = MultiProgress::new();
+/// Runs a number of simulations and returns one result row per simulation
+type Simulate = Box<dyn Fn(i32, &mut dyn RngCore) -> ResultRows + Send + Sync>;
+
+/// Writes chunk results in chunk order no matter which order the chunks finish in
+struct OrderedChunkWriter {
+    writer: RowWriter,
+    next_chunk: usize,
+    pending: BTreeMap<usize, ResultRows>,
+    /// First write error; once set, later chunks are dropped instead of written out of order
+    error: Option<io::Error>,
+}
+
+impl OrderedChunkWriter {
+    fn new(writer: RowWriter) -> Self {
+        OrderedChunkWriter {
+            writer,
+            next_chunk: 0,
+            pending: BTreeMap::new(),
+            error: None,
+        }
+    }
+
+    /// Queues a finished chunk and writes every chunk that is now in sequence.
+    /// The first error is kept for the caller to report when the run ends.
+    fn push(&mut self, chunk_index: usize, results: ResultRows) {
+        if self.error.is_some() {
+            return;
+        }
 
-    for (game_name, game_data) in games.iter() {
-        // Create directory
-        fs::create_dir_all(format!("data/{}", game_name))?;
-
-        // Character banner simulation
-        let pb_char = multi_progress.add(ProgressBar::new(num_simulations));
-        pb_char.set_style(ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} ({eta})")
-            .unwrap());
-        pb_char.set_message(format!("{} character pulls", game_name));
-
-        // Create CSV writer for character pulls
-        let char_filepath = format!("data/{}/character.csv", game_name);
-        let char_writer = Arc::new(Mutex::new(create_csv_writer(
-            &char_filepath,
-            "Pulls,Limited,Weapon,FourStar,ThreeStar"
-        )?));
-
-        // Generate chunks of simulations
-        let chunks = (0..num_simulations).step_by(chunk_size as usize)
-            .map(|start| std::cmp::min(chunk_size, num_simulations - start))
-            .collect::<Vec<_>>();
-
-        // Process chunks in parallel
-        chunks.par_iter().for_each(|&chunk_size| {
-            let results = h_simulate_game(game_data, chunk_size as i32, true);
-
-            // Write chunk to CSV
-            let mut writer = char_writer.lock().unwrap();
-            if let Err(e) = write_chunk_to_csv(&mut writer, &results) {
-                eprintln!("Error writing to CSV: {}", e);
+        self.pending.insert(chunk_index, results);
+        while let Some(results) = self.pending.remove(&self.next_chunk) {
+            if let Err(e) = self.writer.write(&results) {
+                self.error = Some(e);
+                self.pending.clear();
+                return;
             }
+            self.next_chunk += 1;
+        }
+    }
+}
 
-            // Update progress bar
-            pb_char.inc(chunk_size);
-        });
+/// A single banner simulation and the output file its results are streamed to
+struct BannerRun {
+    /// Game the run belongs to, as selected with `--games`
+    game: String,
+    /// Banner name within the game, as selected with `--banners`; also the default output file name
+    banner: String,
+    /// Progress bar label, also the key of the run's RNG streams
+    label: String,
+    /// Game rules and player state the run was simulated with, recorded in its metadata
+    parameters: Value,
+    record: &'static str,
+    columns: &'static [Column],
+    simulate: Simulate,
+}
 
-        // Flush the writer
-        let mut writer = char_writer.lock().unwrap();
-        writer.flush()?;
-        drop(writer);
+impl BannerRun {
+    /// Wraps a simulation returning typed records, taking the columns from the record type
+    fn new<R: Record>(game: &str, banner: &str, label: String, parameters: Value, simulate: impl Fn(i32, &mut dyn RngCore) -> Vec<R> + Send + Sync + 'static) -> Self {
+        BannerRun {
+            game: game.to_string(),
+            banner: banner.to_string(),
+            label,
+            parameters,
+            record: R::NAME,
+            columns: R::COLUMNS,
+            simulate: Box::new(move |n, rng| {
+                let mut rows = Vec::with_capacity(n as usize * R::COLUMNS.len());
+                for record in simulate(n, rng) {
+                    record.push_values(&mut rows);
+                }
+                rows
+            }),
+        }
+    }
+}
 
-        pb_char.finish_with_message(format!("{} character pulls completed", game_name));
-
-        // Weapon banner simulation
-        let pb_weapon = multi_progress.add(ProgressBar::new(num_simulations));
-        pb_weapon.set_style(ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} ({eta})")
-            .unwrap());
-        pb_weapon.set_message(format!("{} weapon pulls", game_name));
-
-        // Create CSV writer for weapon pulls
-        let weapon_filepath = format!("data/{}/weapon.csv", game_name);
-        let weapon_writer = Arc::new(Mutex::new(create_csv_writer(
-            &weapon_filepath,
-            "Pulls,Limited,Weapon,FourStar,ThreeStar"
-        )?));
-
-        // Process chunks in parallel
-        chunks.par_iter().for_each(|&chunk_size| {
-            let results = h_simulate_game(game_data, chunk_size as i32, false);
-
-            // Write chunk to CSV
-            let mut writer = weapon_writer.lock().unwrap();
-            if let Err(e) = write_chunk_to_csv(&mut writer, &results) {
-                eprintln!("Error writing to CSV: {}", e);
+/// Games and banners chosen on the command line; an empty list selects every game or banner
+#[derive(Clone, Debug, Default)]
+struct Selection {
+    games: Vec<String>,
+    banners: Vec<String>,
+}
+
+impl Selection {
+    fn includes_game(&self, game: &str) -> bool {
+        self.games.is_empty() || self.games.iter().any(|selected| selected == game)
+    }
+
+    fn includes(&self, game: &str, banner: &str) -> bool {
+        self.includes_game(game) && (self.banners.is_empty() || self.banners.iter().any(|selected| selected == banner))
+    }
+
+    /// Checks that every selected game is one of the available games
+    fn check_games<'a>(&self, available: impl Iterator<Item = &'a str> + Clone) -> io::Result<()> {
+        for game in &self.games {
+            if !available.clone().any(|existing| existing == game) {
+                let names: Vec<&str> = available.collect();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown game '{}', expected one of: {}", game, names.join(", ")),
+                ));
             }
+        }
+        Ok(())
+    }
 
-            // Update progress bar
-            pb_weapon.inc(chunk_size);
-        });
+    /// Keeps the selected runs, rejecting games and banners that match none of them
+    fn select(&self, runs: Vec<BannerRun>) -> io::Result<Vec<BannerRun>> {
+        let mut games: Vec<&str> = runs.iter().map(|run| run.game.as_str()).collect();
+        games.dedup();
+        self.check_games(games.iter().copied())?;
 
-        // Flush the writer
-        let mut writer = weapon_writer.lock().unwrap();
-        writer.flush()?;
-        drop(writer);
+        for banner in &self.banners {
+            if !runs.iter().any(|run| self.includes_game(&run.game) && &run.banner == banner) {
+                let mut names: Vec<&str> = runs.iter()
+                    .filter(|run| self.includes_game(&run.game))
+                    .map(|run| run.banner.as_str())
+                    .collect();
+                names.sort_unstable();
+                names.dedup();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown banner '{}', expected one of: {}", banner, names.join(", ")),
+                ));
+            }
+        }
 
-        pb_weapon.finish_with_message(format!("{} weapon pulls completed", game_name));
+        Ok(runs.into_iter().filter(|run| self.includes(&run.game, &run.banner)).collect())
     }
+}
 
-    Ok(())
+/// Prints every game and its banners, grouped by the mode that simulates them. `--banners` picks
+/// from the runs of the chosen mode only, so the same banner name can mean different runs per mode.
+fn list_games(modes: &[(&str, Vec<BannerRun>)]) {
+    for (mode, runs) in modes {
+        println!("{}:", mode);
+
+        let mut games: Vec<(&str, Vec<&str>)> = Vec::new();
+        for run in runs {
+            match games.last_mut() {
+                Some((game, banners)) if *game == run.game => banners.push(&run.banner),
+                _ => games.push((&run.game, vec![&run.banner])),
+            }
+        }
+
+        for (game, banners) in games {
+            println!("  {}: {}", game, banners.join(", "));
+        }
+    }
 }
 
-/// Simulates other games with streaming CSV output
-fn simulate_other_games(num_simulations: u64, chunk_size: u64) -> io::Result<()> {
-    let multi_progress = MultiProgress::new();
+/// Builds a HoYoverse simulation that pulls until the target is complete
+fn hoyo_run(game_name: &str, banner_name: &str, game_data: &GameData, start: &StartingState, target: Target) -> BannerRun {
+    let game_data = game_data.clone();
+    let start = *start;
+
+    BannerRun::new(
+        game_name,
+        banner_name,
+        format!("{} {}", game_name, banner_name),
+        json!({ "game": game_data, "start": start, "target": target }),
+        move |n, rng| h_simulate_target(&game_data, &start, &target, n, rng),
+    )
+}
+
+/// Builds the pulls-between-4-stars simulation for one HoYoverse banner
+fn hoyo_four_star_run(game_name: &str, banner_name: &str, banner: fn(&BannerDefinition) -> HoyoBanner, definition: &BannerDefinition) -> BannerRun {
+    let definition = definition.clone();
+
+    BannerRun::new(
+        game_name,
+        &format!("{}_four_star", banner_name),
+        format!("{} {} 4★", game_name, banner_name),
+        json!({ "definition": definition }),
+        move |n, rng| simulate_four_star_gaps(&mut banner(&definition), n, rng),
+    )
+}
+
+/// Builds the multi-copy target simulation for every configured HoYoverse game
+fn target_runs(config: &BannerConfig, start: &StartingState, target: Target) -> Vec<BannerRun> {
+    config.hoyo.iter()
+        .map(|(game_name, game_data)| hoyo_run(game_name, "target", game_data, start, target))
+        .collect()
+}
+
+/// Builds the Wuthering Waves simulation that pulls until the target is complete
+fn wuwa_target_run(config: &BannerConfig, target: Target) -> BannerRun {
+    let resonator_rates = config.wuwa.clone();
+    let weapon_rates = config.wuwa_weapon.clone();
+    BannerRun::new(
+        "wuwa",
+        "target",
+        "Wuwa target".to_string(),
+        json!({ "resonator_rates": resonator_rates, "weapon_rates": weapon_rates, "target": target }),
+        move |n, rng| w_simulate_target(&resonator_rates, &weapon_rates, &target, n, rng),
+    )
+}
+
+/// Builds the Arknights simulation that spends the given pulls on each banner in turn
+fn sequence_run(config: &BannerConfig, sequence: Vec<SequenceStep>, spending: Spending) -> BannerRun {
+    let arknights_rates = config.arknights.clone();
+    BannerRun::new(
+        "arknights",
+        "sequence",
+        "Arknights sequence".to_string(),
+        json!({ "rates": arknights_rates, "sequence": sequence, "spending": spending }),
+        move |n, rng| a_simulate_sequence(&arknights_rates, &sequence, &spending, n, rng),
+    )
+}
+
+/// Builds the standard banner simulation for every configured game
+fn standard_runs(config: &BannerConfig) -> Vec<BannerRun> {
+    config.standard.iter()
+        .map(|(game_name, definition)| {
+            let definition = definition.clone();
+            BannerRun::new(
+                game_name,
+                "standard",
+                format!("{} standard", game_name),
+                json!({ "definition": definition }),
+                move |n, rng| simulate_standard(&definition, n, rng),
+            )
+        })
+        .collect()
+}
+
+/// Builds the list of banner simulations for every configured game. This is the registry that
+/// `--games`, `--banners` and `list-games` draw from; runs of the same game are kept together.
+fn banner_runs(config: &BannerConfig, start: &StartingState, spending: Spending) -> Vec<BannerRun> {
+    let mut runs = Vec::new();
+
+    for (game_name, game_data) in &config.hoyo {
+        // One featured item on each banner
+        runs.push(hoyo_run(game_name, "character", game_data, start, Target::new(1, 0, PullOrder::CharacterFirst)));
+        runs.push(hoyo_run(game_name, "weapon", game_data, start, Target::new(0, 1, PullOrder::WeaponFirst)));
+        runs.push(hoyo_four_star_run(game_name, "character", HoyoBanner::character, &game_data.character));
+        runs.push(hoyo_four_star_run(game_name, "weapon", HoyoBanner::weapon, &game_data.weapon));
+    }
+
+    let wuwa_rates = config.wuwa.clone();
+    runs.push(BannerRun::new(
+        "wuwa",
+        "wuwa",
+        "Wuwa".to_string(),
+        json!({ "rates": wuwa_rates }),
+        move |n, rng| w_simulate_game(WuwaBanner::resonator(&wuwa_rates), n, rng),
+    ));
+
+    let wuwa_weapon_rates = config.wuwa_weapon.clone();
+    runs.push(BannerRun::new(
+        "wuwa",
+        "weapon",
+        "Wuwa weapon".to_string(),
+        json!({ "rates": wuwa_weapon_rates }),
+        move |n, rng| w_simulate_game(WuwaBanner::weapon(&wuwa_weapon_rates), n, rng),
+    ));
 
-    // Ensure directories exist
-    fs::create_dir_all("data/wuwa")?;
-    fs::create_dir_all("data/arknights")?;
+    let wuwa_rates = config.wuwa.clone();
+    runs.push(BannerRun::new(
+        "wuwa",
+        "wuwa_four_star",
+        "Wuwa 4★".to_string(),
+        json!({ "rates": wuwa_rates }),
+        move |n, rng| simulate_four_star_gaps(&mut WuwaBanner::resonator(&wuwa_rates), n, rng),
+    ));
 
-    // Generate chunks of simulations
+    let arknights_rates = config.arknights.clone();
+    runs.push(BannerRun::new(
+        "arknights",
+        "arknights",
+        "Arknights".to_string(),
+        json!({ "rates": arknights_rates, "spending": spending }),
+        move |n, rng| a_simulate_game(&arknights_rates, &spending, n, rng),
+    ));
+
+    for (banner_name, definition) in &config.headhunting {
+        let arknights_rates = config.arknights.clone();
+        let definition = definition.clone();
+        runs.push(BannerRun::new(
+            "arknights",
+            banner_name,
+            format!("Arknights {}", banner_name),
+            json!({ "rates": arknights_rates, "definition": definition, "spending": spending }),
+            move |n, rng| a_simulate_headhunting(&arknights_rates, &definition, &spending, n, rng),
+        ));
+    }
+
+    runs
+}
+
+/// Runs `num_simulations` in parallel chunks of at most `chunk_size` and returns each chunk's result
+/// in chunk order. Every chunk draws from its own RNG stream derived from the seed, the run key and
+/// the chunk index, so results do not depend on the thread count.
+fn run_chunks<T: Send>(num_simulations: u64, chunk_size: u64, seed: u64, key: &str, simulate: impl Fn(usize, u64, &mut SimRng) -> T + Sync) -> Vec<T> {
     let chunks = (0..num_simulations).step_by(chunk_size as usize)
         .map(|start| std::cmp::min(chunk_size, num_simulations - start))
         .collect::<Vec<_>>();
 
-    // Wuwa simulation
-    let pb_wuwa = multi_progress.add(ProgressBar::new(num_simulations));
-    pb_wuwa.set_style(ProgressStyle::default_bar()
+    chunks.par_iter().enumerate()
+        .map(|(chunk_index, &chunk_size)| {
+            let mut rng = chunk_rng(seed, key, chunk_index as u64);
+            simulate(chunk_index, chunk_size, &mut rng)
+        })
+        .collect()
+}
+
+/// Simulates a banner in parallel chunks with streaming output.
+/// Each chunk draws from its own RNG stream derived from the seed, so output is reproducible.
+fn simulate_banner(run: &BannerRun, output: &Output, multi_progress: &MultiProgress, num_simulations: u64, chunk_size: u64, seed: u64) -> io::Result<()> {
+    let pb = multi_progress.add(ProgressBar::new(num_simulations));
+    pb.set_style(ProgressStyle::default_bar()
         .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} ({eta})")
         .unwrap());
-    pb_wuwa.set_message("Wuwa pulls");
+    pb.set_message(format!("{} pulls", run.label));
+    let started = Utc::now();
 
-    // Create CSV writer for Wuwa
-    let wuwa_writer = Arc::new(Mutex::new(create_csv_writer(
-        "data/wuwa/wuwa.csv",
-        "Pulls,FiveStar,FourStar,LimitedFourStar,ThreeStar"
-    )?));
+    // Create the output writer
+    let (destination, writer) = output.create_run(run)?;
+    let writer = Arc::new(Mutex::new(OrderedChunkWriter::new(writer)));
+    // Set on the first write error so the remaining chunks are skipped instead of simulated
+    let failed = AtomicBool::new(false);
 
-    // Process chunks in parallel
-    chunks.par_iter().for_each(|&chunk_size| {
-        let results = w_simulate_game(chunk_size as i32);
+    run_chunks(num_simulations, chunk_size, seed, &run.label, |chunk_index, chunk_size, rng| {
+        if failed.load(Ordering::Relaxed) {
+            return;
+        }
+        let results = (run.simulate)(chunk_size as i32, rng);
 
-        // Write chunk to CSV
-        let mut writer = wuwa_writer.lock().unwrap();
-        if let Err(e) = write_chunk_to_csv(&mut writer, &results) {
-            eprintln!("Error writing to CSV: {}", e);
+        // Write chunk to the output file
+        let mut writer = writer.lock().unwrap();
+        writer.push(chunk_index, results);
+        if writer.error.is_some() {
+            failed.store(true, Ordering::Relaxed);
+            return;
         }
+        drop(writer);
 
         // Update progress bar
-        pb_wuwa.inc(chunk_size);
+        pb.inc(chunk_size);
     });
 
-    // Flush the writer
-    let mut writer = wuwa_writer.lock().unwrap();
-    writer.flush()?;
-    drop(writer);
+    // Flush the writer; every chunk has been written once the parallel loop returns
+    let writer = Arc::try_unwrap(writer).ok().expect("chunk writer still shared").into_inner().unwrap();
+    if let Some(e) = writer.error {
+        pb.abandon_with_message(format!("{} pulls failed", run.label));
+        return Err(io::Error::new(e.kind(), format!("error writing to {}: {}", destination, e)));
+    }
+    writer.writer.finish()?;
 
-    pb_wuwa.finish_with_message("Wuwa pulls completed");
+    pb.finish_with_message(format!("{} pulls completed", run.label));
 
-    // Arknights simulation
-    let pb_arknights = multi_progress.add(ProgressBar::new(num_simulations));
-    pb_arknights.set_style(ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} ({eta})")
-        .unwrap());
-    pb_arknights.set_message("Arknights pulls");
+    let metadata = Metadata {
+        seed: Some(seed),
+        simulations: Some(num_simulations),
+        chunk_size: Some(chunk_size),
+        threads: Some(rayon::current_num_threads()),
+        ..Metadata::new(&run.game, &run.banner, &destination, run.record, run.columns, &run.parameters, started)
+    };
+    output.write_metadata(&run.game, &run.banner, output.format.extension(), &metadata)
+}
 
-    // Create CSV writer for Arknights
-    let arknights_writer = Arc::new(Mutex::new(create_csv_writer(
-        "data/arknights/arknights.csv",
-        "Pulls,SixStar,FiveStar,FourStar,ThreeStar"
-    )?));
+/// Simulates each run in turn; stdout can only take a single run's output. Every destination,
+/// including the exact distribution files written afterwards, is checked before anything is
+/// simulated, and the schema is written once the checks pass.
+fn simulate_runs(runs: Vec<BannerRun>, exact_files: &[(String, String)], output: &Output, multi_progress: &MultiProgress, num_simulations: u64, chunk_size: u64, seed: u64) -> io::Result<()> {
+    if output.dir.is_none() && runs.len() != 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("writing to stdout needs exactly one banner but {} are selected; choose one with simulate --game and --banner", runs.len()),
+        ));
+    }
+    let data_files = runs.iter().map(|run| (run.game.as_str(), run.banner.as_str(), output.format.extension()));
+    let exact_files = exact_files.iter().map(|(game, banner)| (game.as_str(), banner.as_str(), "csv"));
+    let files: Vec<_> = data_files.chain(exact_files).collect();
+    output.check_distinct(files.iter().map(|&(game, banner, _)| (game, banner)))?;
+    output.check_clobber(files)?;
+    output.write_schema()?;
 
-    // Process chunks in parallel
-    chunks.par_iter().for_each(|&chunk_size| {
-        let results = a_simulate_game(chunk_size as i32);
+    for run in runs {
+        simulate_banner(&run, output, multi_progress, num_simulations, chunk_size, seed)?;
+    }
+    Ok(())
+}
 
-        // Write chunk to CSV
-        let mut writer = arknights_writer.lock().unwrap();
-        if let Err(e) = write_chunk_to_csv(&mut writer, &results) {
-            eprintln!("Error writing to CSV: {}", e);
+/// Reports how often Capturing Radiance decides the 50/50 for games that have it.
+/// Every chunk is one player pulling that many consecutive banners for the featured character.
+fn report_radiance(config: &BannerConfig, selection: &Selection, num_simulations: u64, chunk_size: u64, seed: u64) {
+    for (game_name, game_data) in &config.hoyo {
+        if game_data.character.capturing_radiance.is_none() || !selection.includes(game_name, "character") {
+            continue;
         }
 
-        // Update progress bar
-        pb_arknights.inc(chunk_size);
-    });
+        let key = format!("{} radiance", game_name);
 
-    // Flush the writer
-    let mut writer = arknights_writer.lock().unwrap();
-    writer.flush()?;
-    drop(writer);
+        let (featured_rolls, featured_wins, radiance_wins) = run_chunks(num_simulations, chunk_size, seed, &key, |_, chunk_size, rng| {
+            let (rolls, wins, radiance) = h_simulate_radiance(game_data, chunk_size as i32, rng);
+            (rolls as u64, wins as u64, radiance as u64)
+        })
+        .into_iter()
+        .fold((0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
 
-    pb_arknights.finish_with_message("Arknights pulls completed");
+        // Without Capturing Radiance every forced win would have been an ordinary 50/50
+        let featured_chance = game_data.character.featured_chance;
+        println!(
+            "{}: 50/50s won {:.2}% of the time against a base {:.2}%; Capturing Radiance decided {:.2}% of them",
+            game_name,
+            100.0 * featured_wins as f64 / featured_rolls.max(1) as f64,
+            100.0 * featured_chance,
+            100.0 * radiance_wins as f64 / featured_rolls.max(1) as f64
+        );
+    }
+}
 
-    Ok(())
+/// 95% Wilson score interval for a binomial success rate
+fn wilson_interval(successes: u64, trials: u64) -> (f64, f64) {
+    let z = 1.96;
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let denominator = 1.0 + z * z / n;
+    let centre = (p + z * z / (2.0 * n)) / denominator;
+    let margin = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+    (centre - margin, centre + margin)
+}
+
+/// Estimates, for every given HoYoverse game, the chance of reaching the target within the pull budget
+fn simulate_budget(games: &[(String, GameData)], start: &StartingState, target: &Target, budget: i32, num_simulations: u64, chunk_size: u64, seed: u64) {
+    for (game_name, game_data) in games {
+        let key = format!("{} budget", game_name);
+
+        let successes: u64 = run_chunks(num_simulations, chunk_size, seed, &key, |_, chunk_size, rng| {
+            h_simulate_budget(game_data, start, target, budget, chunk_size as i32, rng) as u64
+        })
+        .into_iter()
+        .sum();
+
+        let (low, high) = wilson_interval(successes, num_simulations);
+        println!(
+            "{}: {:.2}% chance (95% CI {:.2}%-{:.2}%) of {} character, {} weapon and {} featured 4★ copies within {} pulls",
+            game_name,
+            100.0 * successes as f64 / num_simulations as f64,
+            100.0 * low,
+            100.0 * high,
+            target.character_copies,
+            target.weapon_copies,
+            target.four_star_copies,
+            budget
+        );
+    }
+}
+
+/// Exits with a usage error if two conflicting options were given. clap only checks the options
+/// given on one side of the subcommand name against each other; the subcommand's matches also
+/// hold the global options given before it, so checking them catches conflicts across the two.
+fn check_conflicts(command: &mut Command, matches: &ArgMatches) {
+    let Some((name, sub_matches)) = matches.subcommand() else {
+        return;
+    };
+    let subcommand = command.find_subcommand(name).expect("parsed subcommand exists");
+    let given = |arg: &Arg| sub_matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine);
+
+    let conflict = subcommand.get_arguments()
+        .filter(|arg| given(arg))
+        .find_map(|arg| {
+            subcommand.get_arg_conflicts_with(arg).into_iter()
+                .find(|other| given(other))
+                .map(|other| format!("the argument '{}' cannot be used with '{}'", arg, other))
+        });
+    if let Some(message) = conflict {
+        command.error(ErrorKind::ArgumentConflict, message).exit();
+    }
 }
 
 fn main() -> io::Result<()> {
-    let matches = Command::new("Gacha Simulator")
+    let mut command = Command::new("Gacha Simulator")
             .arg(
                 Arg::new("simulations")
+                    .global(true)
                     .short('n')
                     .long("simulations")
                     .help("Number of simulations to run")
@@ -228,27 +954,411 @@ fn main() -> io::Result<()> {
             )
             .arg(
                 Arg::new("chunk-size")
+                    .global(true)
                     .short('c')
                     .long("chunk-size")
                     .help("Size of chunks for processing")
                     .default_value("10000")
                     .value_parser(clap::value_parser!(u64))
             )
-            .get_matches();
+            .arg(
+                Arg::new("format")
+                    .global(true)
+                    .long("format")
+                    .help("Output format: csv, json for JSON Lines keyed by column name, or parquet")
+                    .default_value("csv")
+                    .value_parser(OutputFormat::from_str)
+            )
+            .arg(
+                Arg::new("output-dir")
+                    .global(true)
+                    .short('o')
+                    .long("output-dir")
+                    .help("Directory to write output files to, or - to write the single selected banner to stdout")
+                    .default_value("data")
+                    .value_parser(clap::value_parser!(PathBuf))
+            )
+            .arg(
+                Arg::new("file-name")
+                    .global(true)
+                    .long("file-name")
+                    .help("Output file name inside the output directory, without the extension; {game}, {banner}, {seed} and {timestamp} are filled in")
+                    .default_value("{game}/{banner}")
+            )
+            .arg(
+                Arg::new("no-clobber")
+                    .global(true)
+                    .long("no-clobber")
+                    .help("Fail instead of overwriting an existing output file")
+                    .action(ArgAction::SetTrue)
+            )
+            .arg(
+                Arg::new("banner-config")
+                    .global(true)
+                    .long("banner-config")
+                    .help("TOML or JSON file with banner definitions overriding the built-in ones")
+                    .value_parser(clap::value_parser!(PathBuf))
+            )
+            .arg(
+                Arg::new("seed")
+                    .global(true)
+                    .short('s')
+                    .long("seed")
+                    .help("Master seed; runs with the same seed, simulations and chunk size produce identical output")
+                    .value_parser(clap::value_parser!(u64))
+            )
+            .arg(
+                Arg::new("threads")
+                    .global(true)
+                    .short('t')
+                    .long("threads")
+                    .help("Number of worker threads (defaults to the number of CPUs)")
+                    .value_parser(clap::value_parser!(usize))
+            )
+            .arg(
+                Arg::new("character-pity")
+                    .global(true)
+                    .long("character-pity")
+                    .help("HoYoverse character banner pity to start from")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(i32))
+            )
+            .arg(
+                Arg::new("weapon-pity")
+                    .global(true)
+                    .long("weapon-pity")
+                    .help("HoYoverse weapon banner pity to start from")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(i32))
+            )
+            .arg(
+                Arg::new("four-star-pity")
+                    .global(true)
+                    .long("four-star-pity")
+                    .help("HoYoverse 4-star pity to start from on both banners")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(i32))
+            )
+            .arg(
+                Arg::new("character-guaranteed")
+                    .global(true)
+                    .long("character-guaranteed")
+                    .help("Start with the next HoYoverse character 5-star guaranteed to be featured")
+                    .action(ArgAction::SetTrue)
+            )
+            .arg(
+                Arg::new("weapon-guaranteed")
+                    .global(true)
+                    .long("weapon-guaranteed")
+                    .help("Start with the next HoYoverse weapon 5-star guaranteed to be featured")
+                    .action(ArgAction::SetTrue)
+            )
+            .arg(
+                Arg::new("fate-points")
+                    .global(true)
+                    .long("fate-points")
+                    .help("Epitomized Path fate points to start the weapon banner with")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(i32))
+            )
+            .arg(
+                Arg::new("target")
+                    .global(true)
+                    .long("target")
+                    .help("HoYoverse goal such as \"C2 + R1\" or \"E6 + S5\"; writes the pulls needed to data/<game>/target.csv")
+            )
+            .arg(
+                Arg::new("standard")
+                    .global(true)
+                    .long("standard")
+                    .help("Simulate the standard banners instead; writes the pulls to own a specific standard character to data/<game>/standard.csv")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["target", "budget", "arknights-sequence", "wuwa-target"])
+            )
+            .arg(
+                Arg::new("arknights-sequence")
+                    .global(true)
+                    .long("arknights-sequence")
+                    .help("Arknights banners and pulls spent on each, such as \"standard:50,limited:300\"; writes the pity each banner starts with to data/arknights/sequence.csv")
+                    .conflicts_with_all(["target", "budget", "wuwa-target"])
+            )
+            .arg(
+                Arg::new("arknights-rolls")
+                    .global(true)
+                    .long("arknights-rolls")
+                    .help("How Arknights pulls are bought: single or ten (a goal reached mid-batch still completes the batch)")
+                    .default_value("single")
+                    .value_parser(Purchase::from_str)
+            )
+            .arg(
+                Arg::new("arknights-currency")
+                    .global(true)
+                    .long("arknights-currency")
+                    .help("Currency for the Arknights Cost column: orundum or permits")
+                    .default_value("orundum")
+                    .value_parser(Currency::from_str)
+            )
+            .arg(
+                Arg::new("wuwa-target")
+                    .global(true)
+                    .long("wuwa-target")
+                    .help("Wuthering Waves goal such as \"S0 + R1\"; writes the pulls needed to data/wuwa/target.csv")
+                    // --target is the HoYoverse goal; --budget takes it as the copies to reach
+                    .conflicts_with_all(["target", "budget"])
+            )
+            .arg(
+                Arg::new("order")
+                    .global(true)
+                    .long("order")
+                    .help("Banner order for multi-copy goals: character-first, weapon-first or interleaved")
+                    .default_value("character-first")
+                    .value_parser(PullOrder::from_str)
+            )
+            .arg(
+                Arg::new("budget")
+                    .global(true)
+                    .long("budget")
+                    .help("Instead of simulating every banner, estimate the chance of reaching the target copies within this many HoYoverse pulls")
+                    .value_parser(clap::value_parser!(i32).range(0..))
+            )
+            .arg(
+                Arg::new("char-copies")
+                    .global(true)
+                    .long("char-copies")
+                    .help("Featured character copies to obtain in budget mode")
+                    .default_value("1")
+                    .value_parser(clap::value_parser!(i32).range(0..))
+            )
+            .arg(
+                Arg::new("weapon-copies")
+                    .global(true)
+                    .long("weapon-copies")
+                    .help("Featured weapon copies to obtain in budget mode, pulled in the --order banner order")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(i32).range(0..))
+            )
+            .arg(
+                Arg::new("four-star-copies")
+                    .global(true)
+                    .long("four-star-copies")
+                    .help("Copies of one featured 4★ character to obtain on the character banner alongside the target")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(i32).range(0..))
+            )
+            .subcommand(
+                Command::new("simulate")
+                    .about("Simulate the selected games and banners (running without a subcommand simulates everything)")
+                    .arg(
+                        Arg::new("games")
+                            .long("games")
+                            .visible_alias("game")
+                            .help("Comma-separated games to simulate, such as \"hsr,zzz\" (default: all)")
+                            .value_delimiter(',')
+                    )
+                    .arg(
+                        Arg::new("banners")
+                            .long("banners")
+                            .visible_alias("banner")
+                            .help("Comma-separated banners to simulate in each selected game, such as \"weapon\" (default: all)")
+                            .value_delimiter(',')
+                            // Budget mode pulls on both banners of each game
+                            .conflicts_with("budget")
+                    )
+            )
+            .subcommand(
+                Command::new("list-games")
+                    .about("List the games and banners that can be simulated")
+            );
+    let matches = command.get_matches_mut();
+    check_conflicts(&mut command, &matches);
+
+    // The 4★ copies are part of a goal, and only --target and --budget pull for one
+    let goal = matches.get_one::<String>("target").is_some() || matches.get_one::<i32>("budget").is_some();
+    if matches.value_source("four-star-copies") == Some(ValueSource::CommandLine) && !goal {
+        command.error(ErrorKind::MissingRequiredArgument, "--four-star-copies needs a --target or --budget goal").exit();
+    }
 
     let num_simulations = matches.get_one::<u64>("simulations").cloned().unwrap_or(1000000);
     let chunk_size = matches.get_one::<u64>("chunk-size").cloned().unwrap_or(10000);
+    let seed = matches.get_one::<u64>("seed").cloned().unwrap_or_else(|| rand::thread_rng().gen());
+    let threads = matches.get_one::<usize>("threads").cloned().unwrap_or_else(num_cpus::get);
+    let output_dir = matches.get_one::<PathBuf>("output-dir").cloned().unwrap_or_else(|| PathBuf::from("data"));
+    let output = Output {
+        dir: (output_dir != Path::new("-")).then_some(output_dir),
+        template: matches.get_one::<String>("file-name").cloned().unwrap_or_else(|| "{game}/{banner}".to_string()),
+        format: matches.get_one::<OutputFormat>("format").cloned().unwrap_or(OutputFormat::Csv),
+        seed,
+        timestamp: Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+        no_clobber: matches.get_flag("no-clobber"),
+    };
+    output.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let start = StartingState {
+        character_pity: matches.get_one::<i32>("character-pity").cloned().unwrap_or(0),
+        weapon_pity: matches.get_one::<i32>("weapon-pity").cloned().unwrap_or(0),
+        four_star_pity: matches.get_one::<i32>("four-star-pity").cloned().unwrap_or(0),
+        character_guaranteed: matches.get_flag("character-guaranteed"),
+        weapon_guaranteed: matches.get_flag("weapon-guaranteed"),
+        fate_points: matches.get_one::<i32>("fate-points").cloned().unwrap_or(0),
+    };
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .map_err(io::Error::other)?;
+
+    let config = match matches.get_one::<PathBuf>("banner-config") {
+        Some(path) => BannerConfig::load(path)?,
+        None => BannerConfig::default(),
+    };
+
+    for (game_name, game_data) in &config.hoyo {
+        start.validate(game_data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", game_name, e)))?;
+    }
 
-    println!("Starting simulations with {} total pulls for each game type", num_simulations);
-    println!("Using chunk size of {} for memory efficiency", chunk_size);
+    let selection = match matches.subcommand() {
+        Some(("simulate", simulate)) => Selection {
+            games: simulate.get_many::<String>("games").map(|games| games.cloned().collect()).unwrap_or_default(),
+            banners: simulate.get_many::<String>("banners").map(|banners| banners.cloned().collect()).unwrap_or_default(),
+        },
+        _ => Selection::default(),
+    };
 
-    // Simulate Honkai games
-    simulate_hoyo_games(num_simulations, chunk_size)?;
+    let spending = Spending {
+        purchase: matches.get_one::<Purchase>("arknights-rolls").cloned().unwrap_or(Purchase::Single),
+        currency: matches.get_one::<Currency>("arknights-currency").cloned().unwrap_or(Currency::Orundum),
+    };
 
-    // Simulate Wuwa and Arknights games
-    simulate_other_games(num_simulations, chunk_size)?;
+    if matches.subcommand_name() == Some("list-games") {
+        let target = Target::new(1, 0, PullOrder::CharacterFirst);
+        list_games(&[
+            ("default", banner_runs(&config, &start, spending)),
+            ("--standard", standard_runs(&config)),
+            ("--target", target_runs(&config, &start, target)),
+            ("--wuwa-target", vec![wuwa_target_run(&config, target)]),
+            ("--arknights-sequence", vec![sequence_run(&config, Vec::new(), spending)]),
+        ]);
+        return Ok(());
+    }
+
+    let order = matches.get_one::<PullOrder>("order").cloned().unwrap_or(PullOrder::CharacterFirst);
+    let four_star_copies = matches.get_one::<i32>("four-star-copies").cloned().unwrap_or(0);
+    let target = match matches.get_one::<String>("target") {
+        Some(spec) => Some(Target {
+            four_star_copies,
+            ..Target::parse(spec, order).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        }),
+        None => None,
+    };
+
+    if let Some(&budget) = matches.get_one::<i32>("budget") {
+        let target = target.unwrap_or_else(|| Target {
+            four_star_copies,
+            ..Target::new(
+                matches.get_one::<i32>("char-copies").cloned().unwrap_or(1),
+                matches.get_one::<i32>("weapon-copies").cloned().unwrap_or(0),
+                order,
+            )
+        });
+
+        selection.check_games(config.hoyo.iter().map(|(game_name, _)| game_name.as_str()))?;
+        let games: Vec<(String, GameData)> = config.hoyo.iter()
+            .filter(|(game_name, _)| selection.includes_game(game_name))
+            .cloned()
+            .collect();
+
+        println!("Running {} budget simulations per game with seed {} on {} threads", num_simulations, seed, threads);
+        simulate_budget(&games, &start, &target, budget, num_simulations, chunk_size, seed);
+        return Ok(());
+    }
+
+    eprintln!("Starting simulations with {} total pulls for each game type", num_simulations);
+    eprintln!("Using chunk size of {} for memory efficiency", chunk_size);
+    eprintln!("Using seed {} on {} threads", seed, threads);
 
-    println!("All simulations completed successfully!");
+    let multi_progress = MultiProgress::new();
+
+    if let Some(spec) = matches.get_one::<String>("arknights-sequence") {
+        let sequence = parse_sequence(spec, &config.headhunting)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        for (index, step) in sequence.iter().enumerate() {
+            eprintln!("Banner {}: {} ({} pulls)", index, step.name, step.pulls);
+        }
+
+        simulate_runs(selection.select(vec![sequence_run(&config, sequence, spending)])?, &[], &output, &multi_progress, num_simulations, chunk_size, seed)?;
+        eprintln!("All simulations completed successfully!");
+        return Ok(());
+    }
+
+    if let Some(spec) = matches.get_one::<String>("wuwa-target") {
+        let target = parse_wuwa_target(spec, order)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        eprintln!(
+            "Simulating {} resonator and {} weapon copies ({:?})",
+            target.character_copies, target.weapon_copies, target.order
+        );
+
+        simulate_runs(selection.select(vec![wuwa_target_run(&config, target)])?, &[], &output, &multi_progress, num_simulations, chunk_size, seed)?;
+        eprintln!("All simulations completed successfully!");
+        return Ok(());
+    }
+
+    if matches.get_flag("standard") {
+        simulate_runs(selection.select(standard_runs(&config))?, &[], &output, &multi_progress, num_simulations, chunk_size, seed)?;
+        eprintln!("All simulations completed successfully!");
+        return Ok(());
+    }
+
+    if let Some(target) = target {
+        eprintln!(
+            "Simulating {} character, {} weapon and {} featured 4★ copies ({:?})",
+            target.character_copies, target.weapon_copies, target.four_star_copies, target.order
+        );
+        simulate_runs(selection.select(target_runs(&config, &start, target))?, &[], &output, &multi_progress, num_simulations, chunk_size, seed)?;
+        eprintln!("All simulations completed successfully!");
+        return Ok(());
+    }
+
+    // The exact distributions are written alongside the runs, so their names must not clash either
+    let runs = selection.select(banner_runs(&config, &start, spending))?;
+    let exact_files = if output.dir.is_some() { exact_files(&config, &selection) } else { Vec::new() };
+    simulate_runs(runs, &exact_files, &output, &multi_progress, num_simulations, chunk_size, seed)?;
+
+    // Exact distributions and the radiance report would mix with the results on stdout
+    if output.dir.is_some() {
+        // Exact distributions for the HoYoverse and Arknights pity models
+        solve_hoyo_exact(&config, &start, &selection, &output)?;
+        if selection.includes("arknights", "arknights") {
+            solve_arknights_exact(&config, &output)?;
+        }
+
+        report_radiance(&config, &selection, num_simulations, chunk_size, seed);
+    }
+
+    eprintln!("All simulations completed successfully!");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_do_not_depend_on_the_thread_count() {
+        let spending = Spending { purchase: Purchase::Single, currency: Currency::Orundum };
+        let runs = banner_runs(&BannerConfig::default(), &StartingState::default(), spending);
+
+        let simulate_all = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                runs.iter()
+                    .map(|run| run_chunks(1000, 64, 7, &run.label, |_, chunk_size, rng| (run.simulate)(chunk_size as i32, rng)))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(simulate_all(1), simulate_all(4));
+    }
+}
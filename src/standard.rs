@@ -0,0 +1,249 @@
+pub mod standard {
+    use crate::banner::banner::{record, BannerModel, PityRates, PityState, PullOutcome, Rarity};
+    use rand::Rng;
+    use rand::RngCore;
+    use lazy_static::lazy_static;
+    use serde::{Deserialize, Serialize};
+
+    lazy_static! {
+        // Honkai: Star Rail Stellar Warp, with a standard character selector at 300 warps
+        static ref HSR: StandardDefinition = StandardDefinition {
+            pity: PityRates {
+                base_rate: 0.006,
+                soft_pity: 74,
+                soft_pity_increment: 0.062,
+                hard_pity: 90,
+                four_star_rate: 0.051,
+                four_star_pity: 10,
+                four_star_soft_pity: 8,
+                four_star_soft_pity_increment: 0.51,
+            },
+            character_count: 7,
+            weapon_count: 7,
+            character_share: 0.5,
+            selector_pulls: Some(300),
+        };
+
+        // Genshin Impact Wanderlust Invocation, with a chosen standard character at 300 wishes
+        static ref GENSHIN: StandardDefinition = StandardDefinition {
+            pity: PityRates {
+                base_rate: 0.006,
+                soft_pity: 74,
+                soft_pity_increment: 0.062,
+                hard_pity: 90,
+                four_star_rate: 0.051,
+                four_star_pity: 10,
+                four_star_soft_pity: 8,
+                four_star_soft_pity_increment: 0.51,
+            },
+            character_count: 7,
+            weapon_count: 10,
+            character_share: 0.5,
+            selector_pulls: Some(300),
+        };
+
+        // Zenless Zone Zero Stable Channel
+        static ref ZZZ: StandardDefinition = StandardDefinition {
+            pity: PityRates {
+                base_rate: 0.006,
+                soft_pity: 74,
+                soft_pity_increment: 0.062,
+                hard_pity: 90,
+                four_star_rate: 0.094,
+                four_star_pity: 10,
+                four_star_soft_pity: 8,
+                four_star_soft_pity_increment: 0.51,
+            },
+            character_count: 6,
+            weapon_count: 6,
+            character_share: 0.5,
+            selector_pulls: None,
+        };
+
+        // Wuthering Waves standard convene, with a resonator selector at 50 convenes
+        static ref WUWA: StandardDefinition = StandardDefinition {
+            pity: PityRates {
+                base_rate: 0.008,
+                soft_pity: 65,
+                soft_pity_increment: 0.04,
+                hard_pity: 80,
+                four_star_rate: 0.06,
+                four_star_pity: 10,
+                four_star_soft_pity: 8,
+                four_star_soft_pity_increment: 0.5,
+            },
+            character_count: 5,
+            weapon_count: 5,
+            character_share: 0.5,
+            selector_pulls: Some(50),
+        };
+    }
+
+    /// Rates, 5-star pool and selector of a permanent standard banner
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct StandardDefinition {
+        #[serde(flatten)]
+        pub pity: PityRates,
+        /// Standard 5-star characters in the pool; the first is the one pulled for
+        pub character_count: i32,
+        /// Standard 5-star weapons in the pool
+        pub weapon_count: i32,
+        /// Chance that a 5-star is a character rather than a weapon
+        pub character_share: f64,
+        /// Total pulls after which a standard character of choice is granted once
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub selector_pulls: Option<i32>,
+    }
+
+    /// Built-in standard banners for every supported game, in simulation order
+    pub fn builtin_standard() -> Vec<(String, StandardDefinition)> {
+        vec![
+            ("hsr".to_string(), HSR.clone()),
+            ("genshin".to_string(), GENSHIN.clone()),
+            ("zzz".to_string(), ZZZ.clone()),
+            ("wuwa".to_string(), WUWA.clone()),
+        ]
+    }
+
+    impl StandardDefinition {
+        /// Checks that rates are probabilities and pity thresholds and pool sizes are consistent
+        pub fn validate(&self) -> Result<(), String> {
+            self.pity.validate()?;
+            if !(0.0..=1.0).contains(&self.character_share) {
+                return Err(format!("character_share must be between 0 and 1, got {}", self.character_share));
+            }
+            if self.character_count < 1 || self.weapon_count < 0 {
+                return Err("character_count must be at least 1 and weapon_count at least 0".to_string());
+            }
+            if self.weapon_count == 0 && self.character_share < 1.0 {
+                return Err("character_share must be 1 when weapon_count is 0".to_string());
+            }
+            if self.selector_pulls.is_some_and(|pulls| pulls < 1) {
+                return Err("selector_pulls must be at least 1".to_string());
+            }
+            // Otherwise a simulated player would pull forever for the first standard character
+            if self.character_share == 0.0 && self.selector_pulls.is_none() {
+                return Err("character_share is 0 and there is no selector, so the standard character can never be obtained".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// Permanent banner whose 5-stars come from the standard pool with no featured items
+    pub struct StandardBanner {
+        definition: StandardDefinition,
+        state: PityState,
+    }
+
+    impl StandardBanner {
+        pub fn new(definition: &StandardDefinition) -> Self {
+            StandardBanner {
+                definition: definition.clone(),
+                state: PityState::default(),
+            }
+        }
+    }
+
+    impl BannerModel for StandardBanner {
+        fn pull(&mut self, rng: &mut dyn RngCore) -> PullOutcome {
+            let definition = &self.definition;
+            self.state.pity += 1;
+            self.state.four_star_pity += 1;
+
+            let chance = definition.pity.five_star_chance(self.state.pity);
+            if self.state.pity == definition.pity.hard_pity || rng.gen::<f64>() <= chance {
+                self.state.pity = 0;
+                self.state.four_star_pity = 0;
+
+                // Characters and weapons are drawn uniformly from their pools
+                let character = rng.gen::<f64>() < definition.character_share;
+                let chosen = character && (definition.character_count == 1 || rng.gen_range(0..definition.character_count) == 0);
+                let outcome = PullOutcome { chosen, ..PullOutcome::new(Rarity::Five, false) };
+                return outcome.with_weapon(!character);
+            }
+
+            let four_star_chance = definition.pity.four_star_chance(self.state.four_star_pity);
+            if self.state.four_star_pity == definition.pity.four_star_pity || rng.gen::<f64>() <= four_star_chance {
+                self.state.four_star_pity = 0;
+                return PullOutcome::new(Rarity::Four, false);
+            }
+
+            PullOutcome::new(Rarity::Three, false)
+        }
+
+        fn pity(&self) -> PityState {
+            self.state
+        }
+
+        fn reset(&mut self) {
+            self.state = PityState::default();
+        }
+    }
+
+    record! {
+        /// One player's pulls on a fresh standard banner until they own the first standard character
+        pub struct StandardRecord("standard") {
+            pulls: "Pulls" => "Total pulls until the character was owned",
+            five_star_character: "FiveStarCharacter" => "Standard 5-star characters received",
+            five_star_weapon: "FiveStarWeapon" => "Standard 5-star weapons received",
+            four_star: "FourStar" => "4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            selector: "Selector" => "1 if the character came from the selector, 0 if pulled",
+        }
+    }
+
+    /// Simulates players pulling on a fresh standard banner until they own the first standard
+    /// character, either pulled or taken from the selector.
+    pub fn simulate_standard(definition: &StandardDefinition, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<StandardRecord> {
+        let mut results = Vec::with_capacity(num_simulations as usize);
+        let mut banner = StandardBanner::new(definition);
+
+        for _ in 0..num_simulations {
+            let mut record = StandardRecord::default();
+
+            banner.reset();
+
+            loop {
+                record.pulls += 1;
+                let outcome = banner.pull(rng);
+                match outcome.rarity {
+                    Rarity::Five | Rarity::Six if outcome.weapon => record.five_star_weapon += 1,
+                    Rarity::Five | Rarity::Six => record.five_star_character += 1,
+                    Rarity::Four => record.four_star += 1,
+                    Rarity::Three => record.three_star += 1,
+                }
+
+                if outcome.chosen {
+                    break;
+                }
+                if definition.selector_pulls == Some(record.pulls) {
+                    record.selector = 1;
+                    break;
+                }
+            }
+
+            results.push(record);
+        }
+
+        results
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::banner::banner::SimRng;
+        use rand::SeedableRng;
+
+        #[test]
+        fn rarity_counts_add_up_to_pulls() {
+            let mut rng = SimRng::seed_from_u64(1);
+            for (_, definition) in builtin_standard() {
+                for record in simulate_standard(&definition, 500, &mut rng) {
+                    let five_stars = record.five_star_character + record.five_star_weapon;
+                    assert_eq!(five_stars + record.four_star + record.three_star, record.pulls, "{:?}", record);
+                }
+            }
+        }
+    }
+}
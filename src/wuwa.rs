@@ -1,60 +1,300 @@
 pub mod wuwa {
+    use crate::banner::banner::{record, BannerModel, PityRates, PityState, PullOutcome, Rarity};
+    use crate::hoyo::hoyo::{PullOrder, Target};
     use rand::Rng;
-    use rand::thread_rng;
+    use rand::RngCore;
     use lazy_static::lazy_static;
+    use serde::{Deserialize, Serialize};
 
     lazy_static! {
         static ref COMMON_FIVE_CHANCE: f64 = 0.008;
         static ref COMMON_FOUR_STAR_CHANCE: f64 = 0.06;
         static ref FOUR_STAR_PITY: i32 = 10;
+        static ref FOUR_STAR_SOFT_PITY: i32 = 8;
+        static ref FOUR_STAR_SOFT_PITY_INCREMENT: f64 = 0.5;
         static ref FIVE_STAR_PITY: i32 = 80;
+        static ref SOFT_PITY: i32 = 65;
+        static ref SOFT_PITY_INCREMENT: f64 = 0.04;
     }
 
-    // number of pulls till one five star
-    pub fn w_simulate_game(num_simulations: i32) -> Vec<(i32, i32, i32, i32, i32)> {
+    /// Base rates and pity thresholds for a Wuthering Waves convene
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct WuwaRates {
+        #[serde(flatten)]
+        pub pity: PityRates,
+        /// Chance that a 5-star is the featured resonator
+        #[serde(default = "default_featured_chance")]
+        pub featured_chance: f64,
+        /// Whether losing the featured roll guarantees the next 5-star
+        #[serde(default = "default_guarantee")]
+        pub guarantee: bool,
+        /// Chance that a 4-star is one of the featured 4-stars
+        pub featured_four_star_chance: f64,
+    }
+
+    fn default_featured_chance() -> f64 {
+        0.5
+    }
+
+    fn default_guarantee() -> bool {
+        true
+    }
+
+    impl Default for WuwaRates {
+        fn default() -> Self {
+            WuwaRates {
+                pity: PityRates {
+                    base_rate: *COMMON_FIVE_CHANCE,
+                    soft_pity: *SOFT_PITY,
+                    soft_pity_increment: *SOFT_PITY_INCREMENT,
+                    hard_pity: *FIVE_STAR_PITY,
+                    four_star_rate: *COMMON_FOUR_STAR_CHANCE,
+                    four_star_pity: *FOUR_STAR_PITY,
+                    four_star_soft_pity: *FOUR_STAR_SOFT_PITY,
+                    four_star_soft_pity_increment: *FOUR_STAR_SOFT_PITY_INCREMENT,
+                },
+                featured_chance: 0.5,
+                guarantee: true,
+                featured_four_star_chance: 0.5,
+            }
+        }
+    }
+
+    impl WuwaRates {
+        /// Weapon convene defaults: the same rates and pity, with every 5-star the featured weapon
+        pub fn weapon() -> Self {
+            WuwaRates {
+                featured_chance: 1.0,
+                ..WuwaRates::default()
+            }
+        }
+
+        /// Checks that rates are probabilities and pity thresholds are positive
+        pub fn validate(&self) -> Result<(), String> {
+            self.pity.validate()?;
+            for (name, rate) in [
+                ("featured_chance", self.featured_chance),
+                ("featured_four_star_chance", self.featured_four_star_chance),
+            ] {
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(format!("{} must be between 0 and 1, got {}", name, rate));
+                }
+            }
+            // Otherwise a simulated player would pull forever for the featured item
+            if self.featured_chance == 0.0 && !self.guarantee {
+                return Err("featured_chance is 0 with no guarantee, so the featured item can never be obtained".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// Wuthering Waves convene with featured 50/50s on both 5-stars and 4-stars
+    pub struct WuwaBanner {
+        rates: WuwaRates,
+        /// Whether the banner's 5-stars are weapons rather than resonators
+        weapon_banner: bool,
+        state: PityState,
+    }
+
+    impl WuwaBanner {
+        /// Resonator convene, whose 5-stars are all resonators
+        pub fn resonator(rates: &WuwaRates) -> Self {
+            WuwaBanner::new(rates, false)
+        }
+
+        /// Weapon convene, whose 5-stars are all weapons
+        pub fn weapon(rates: &WuwaRates) -> Self {
+            WuwaBanner::new(rates, true)
+        }
+
+        fn new(rates: &WuwaRates, weapon_banner: bool) -> Self {
+            WuwaBanner {
+                rates: rates.clone(),
+                weapon_banner,
+                state: PityState::default(),
+            }
+        }
+    }
+
+    impl BannerModel for WuwaBanner {
+        fn pull(&mut self, rng: &mut dyn RngCore) -> PullOutcome {
+            self.state.pity += 1;
+            self.state.four_star_pity += 1;
+
+            let five_star_chance = self.rates.pity.five_star_chance(self.state.pity);
+            if self.state.pity == self.rates.pity.hard_pity || rng.gen::<f64>() <= five_star_chance {
+                self.state.pity = 0;
+                self.state.four_star_pity = 0;
+
+                // Featured roll, guaranteed after a loss; the guarantee carries over to the next 5-star
+                let featured = self.state.guaranteed || rng.gen::<f64>() <= self.rates.featured_chance;
+                self.state.guaranteed = !featured && self.rates.guarantee;
+                return PullOutcome::new(Rarity::Five, featured).with_weapon(self.weapon_banner);
+            }
+
+            let four_star_chance = self.rates.pity.four_star_chance(self.state.four_star_pity);
+
+            // A roll of its own, so 5-star soft pity does not swallow the 4-star rate
+            if self.state.four_star_pity == self.rates.pity.four_star_pity || rng.gen::<f64>() <= four_star_chance {
+                let featured = self.state.four_star_guaranteed || rng.gen::<f64>() <= self.rates.featured_four_star_chance;
+                self.state.four_star_guaranteed = !featured;
+                self.state.four_star_pity = 0;
+                return PullOutcome::new(Rarity::Four, featured);
+            }
+
+            PullOutcome::new(Rarity::Three, false)
+        }
+
+        fn pity(&self) -> PityState {
+            self.state
+        }
+
+        fn reset(&mut self) {
+            self.state = PityState::default();
+        }
+    }
+
+    record! {
+        /// One player's pulls on a Wuthering Waves convene until its featured 5-star
+        pub struct WuwaRecord("wuwa") {
+            pulls: "Pulls" => "Total pulls until the featured 5-star",
+            five_star: "FiveStar" => "5-stars received, including lost 50/50s",
+            four_star: "FourStar" => "Off-banner 4-stars received",
+            limited_four_star: "LimitedFourStar" => "Featured 4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            standard_character: "StandardCharacter" => "Standard 5-star resonators from lost 50/50s",
+            standard_weapon: "StandardWeapon" => "Standard 5-star weapons from lost 50/50s",
+        }
+    }
+
+    record! {
+        /// One player's pulls on the resonator and weapon convenes until the target is complete
+        pub struct WuwaTargetRecord("wuwa_target") {
+            pulls: "Pulls" => "Total pulls until the target was complete",
+            resonator: "Resonator" => "Featured 5-star resonators received",
+            weapon: "Weapon" => "Featured 5-star weapons received",
+            five_star: "FiveStar" => "5-stars received, including lost 50/50s",
+            four_star: "FourStar" => "4-stars received",
+            three_star: "ThreeStar" => "3-stars received",
+            standard_character: "StandardCharacter" => "Standard 5-star resonators from lost 50/50s",
+            standard_weapon: "StandardWeapon" => "Standard 5-star weapons from lost 50/50s",
+        }
+    }
+
+    /// Simulates players pulling until the banner's featured 5-star. The 5-star, 4-star and
+    /// 3-star counts of each record add up to its total pulls.
+    pub fn w_simulate_game(mut banner: WuwaBanner, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<WuwaRecord> {
         let mut results = Vec::new();
+
         for _ in 0..num_simulations {
-            let mut pulls = 0;
-            let mut five_successes = 0;
-            let mut four_successes = 0;
-            let mut limited_four_successes = 0;
-            let mut three_successes = 0;
-            let mut curr_five_pity = 0;
-            let mut curr_four_pity = 0;
-            let mut four_guaranteed = false;
+            let mut record = WuwaRecord::default();
+
+            banner.reset();
 
             loop {
-                pulls += 1;
-                let random_value: f64 = thread_rng().gen::<f64>();
-                let five_star_chance = *COMMON_FIVE_CHANCE;
-                let four_star_chance = *COMMON_FOUR_STAR_CHANCE;
-                let five_pity = *FIVE_STAR_PITY;
-                let four_pity = *FOUR_STAR_PITY;
-
-                if curr_five_pity + 1 == five_pity || random_value <= five_star_chance {
-                    five_successes += 1;
-                    curr_five_pity = 0;
-                    break;
+                record.pulls += 1;
+                let outcome = banner.pull(rng);
+                match outcome.rarity {
+                    Rarity::Five | Rarity::Six => {
+                        record.five_star += 1;
+                        if outcome.featured {
+                            break;
+                        }
+                        if outcome.weapon {
+                            record.standard_weapon += 1;
+                        } else {
+                            record.standard_character += 1;
+                        }
+                    }
+                    Rarity::Four if outcome.featured => record.limited_four_star += 1,
+                    Rarity::Four => record.four_star += 1,
+                    Rarity::Three => record.three_star += 1,
+                }
+            }
+            debug_assert_eq!(record.five_star + record.four_star + record.limited_four_star + record.three_star, record.pulls);
+            results.push(record);
+        }
+        results
+    }
+
+    /// Parses a goal such as "S2 + R1": resonance chain level S0-S6 needs one more featured
+    /// resonator copy than the level, weapon rank R1-R5 needs that many featured weapons
+    pub fn parse_target(spec: &str, order: PullOrder) -> Result<Target, String> {
+        Target::parse_levels(spec, order, &['S'], &['R'])
+    }
+
+    /// Simulates players pulling on the resonator and weapon convenes until the target is
+    /// complete. The 5-star, 4-star and 3-star counts of each record add up to its total pulls.
+    pub fn w_simulate_target(resonator_rates: &WuwaRates, weapon_rates: &WuwaRates, target: &Target, num_simulations: i32, rng: &mut dyn RngCore) -> Vec<WuwaTargetRecord> {
+        let mut results = Vec::with_capacity(num_simulations as usize);
+        let mut resonator_banner = WuwaBanner::resonator(resonator_rates);
+        let mut weapon_banner = WuwaBanner::weapon(weapon_rates);
+
+        for _ in 0..num_simulations {
+            let mut record = WuwaTargetRecord::default();
+
+            resonator_banner.reset();
+            weapon_banner.reset();
+
+            while let Some(on_resonator_banner) = target.next_is_character(record.resonator, record.weapon, 0) {
+                record.pulls += 1;
+
+                let outcome = if on_resonator_banner {
+                    resonator_banner.pull(rng)
                 } else {
-                    if curr_four_pity + 1 == four_pity || random_value <= four_star_chance {
-                        if four_guaranteed || thread_rng().gen::<f64>() <= 0.5 {
-                            limited_four_successes += 1;
-                            curr_four_pity = 0;
-                            four_guaranteed = false;
+                    weapon_banner.pull(rng)
+                };
+
+                match outcome.rarity {
+                    Rarity::Five | Rarity::Six => {
+                        record.five_star += 1;
+                        if outcome.featured {
+                            if on_resonator_banner {
+                                record.resonator += 1;
+                            } else {
+                                record.weapon += 1;
+                            }
+                        } else if outcome.weapon {
+                            record.standard_weapon += 1;
                         } else {
-                            four_guaranteed = true;
-                            four_successes += 1;
-                            curr_four_pity = 0;
+                            record.standard_character += 1;
                         }
-                    } else {
-                        three_successes += 1;
                     }
-                    curr_five_pity += 1;
-                    curr_four_pity += 1;
+                    Rarity::Four => record.four_star += 1,
+                    Rarity::Three => record.three_star += 1,
                 }
             }
-            results.push((pulls, five_successes, four_successes, limited_four_successes, three_successes));
+
+            debug_assert_eq!(record.five_star + record.four_star + record.three_star, record.pulls);
+            results.push(record);
         }
+
         results
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::banner::banner::SimRng;
+        use rand::SeedableRng;
+
+        #[test]
+        fn rarity_counts_add_up_to_pulls() {
+            let mut rng = SimRng::seed_from_u64(1);
+            let rates = WuwaRates::default();
+            let weapon_rates = WuwaRates::weapon();
+
+            for banner in [WuwaBanner::resonator(&rates), WuwaBanner::weapon(&weapon_rates)] {
+                for record in w_simulate_game(banner, 500, &mut rng) {
+                    assert_eq!(record.five_star + record.four_star + record.limited_four_star + record.three_star, record.pulls, "{:?}", record);
+                }
+            }
+
+            let target = Target::new(2, 1, PullOrder::Interleaved);
+            for record in w_simulate_target(&rates, &weapon_rates, &target, 500, &mut rng) {
+                assert_eq!(record.five_star + record.four_star + record.three_star, record.pulls, "{:?}", record);
+            }
+        }
+    }
 }